@@ -5,6 +5,7 @@ use std::{
     slice,
 };
 
+use doorstop_core::api::{InjectionMethod, set_injection_method};
 use windows::{
     Win32::{
         Foundation::{HMODULE, TRUE},
@@ -47,6 +48,7 @@ extern "system" fn DllMain(handle: HMODULE, reason: u32, _: *const c_void) -> BO
         let path = HSTRING::from(path.as_os_str());
         let handle = unsafe { LoadLibraryW(&path).unwrap() };
         load_proxy_functions(handle);
+        set_injection_method(InjectionMethod::Proxy);
     }
 
     TRUE
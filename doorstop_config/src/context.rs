@@ -0,0 +1,54 @@
+use std::{env, io, path::PathBuf};
+
+/// Everything [`Config::load`](crate::Config::load) reads from the process, so a config can also be loaded on behalf of another process or in tests.
+#[derive(Debug, Clone, Default)]
+pub struct LoadContext {
+    /// Directory of the game executable (or its app bundle on macOS), `doorstop_config.ini` and `doorstop_disabled` are looked up here.
+    pub game_dir: PathBuf,
+    /// The game's data directory, if it has one.
+    pub data_dir: Option<PathBuf>,
+    /// Name of the game executable without its extension, matched against the profiles' `match` globs.
+    pub executable_name: String,
+    /// Relative paths from the environment and command line are resolved against this directory.
+    pub working_dir: PathBuf,
+    pub home_dir: Option<PathBuf>,
+    /// Command line arguments, including the program name.
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+impl LoadContext {
+    /// Captures the arguments, environment, working and home directories of the current process.
+    pub fn from_process(game_dir: PathBuf, executable_name: String, data_dir: Option<PathBuf>) -> io::Result<Self> {
+        Ok(Self {
+            game_dir,
+            data_dir,
+            executable_name,
+            working_dir: env::current_dir()?,
+            home_dir: env::home_dir(),
+            args: env::args_os().map(|arg| arg.to_string_lossy().into_owned()).collect(),
+            env: env::vars_os()
+                .map(|(name, value)| (name.to_string_lossy().into_owned(), value.to_string_lossy().into_owned()))
+                .collect(),
+        })
+    }
+
+    /// Returns the value of an environment variable.
+    #[must_use]
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(variable, _)| variable_name_eq(variable, name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn has_arg(&self, name: &str) -> bool {
+        self.args.iter().any(|arg| arg.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Compares environment variable names, they are case-insensitive on Windows.
+#[must_use]
+pub fn variable_name_eq(a: &str, b: &str) -> bool {
+    if cfg!(windows) { a.eq_ignore_ascii_case(b) } else { a == b }
+}
@@ -0,0 +1,2006 @@
+mod context;
+mod migrate;
+mod writer;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Display, Formatter},
+    fs, path,
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use anyhow::bail;
+use cfg_if::cfg_if;
+use ini::{Ini, Properties};
+use log::warn;
+
+pub use crate::{
+    context::{LoadContext, variable_name_eq},
+    migrate::migrate_config_file,
+};
+
+const CONFIG_FILE_NAME: &str = "doorstop_config.ini";
+
+const PROFILE_SECTION_PREFIX: &str = "Profile:";
+
+const TARGET_SECTION_PREFIX: &str = "Target:";
+
+const ENVIRONMENT_SECTION: &str = "Environment";
+
+/// Separates the entries of path-like environment variables, such as `PATH`.
+const PATH_LIST_SEPARATOR: char = if cfg!(windows) { ';' } else { ':' };
+
+/// Disables doorstop when present in the game directory, regardless of any other setting.
+const DISABLED_MARKER_FILE_NAME: &str = "doorstop_disabled";
+
+#[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Config {
+    pub file_path: Option<PathBuf>,
+    /// Files merged into the main one, in the order they were loaded.
+    pub fragment_paths: Vec<PathBuf>,
+    /// Relative paths from the environment and command line are resolved against this directory, the ones from the config file against its own directory.
+    pub working_dir: PathBuf,
+    pub profile: Option<String>,
+    pub strict: bool,
+    pub enabled: bool,
+    pub redirect_output_log: bool,
+    /// Where Unity writes its log, `{pid}`, `{timestamp}` and `{exe}` are replaced when the game starts.
+    pub output_log_path: Option<PathBuf>,
+    /// Copies Unity's log to stderr, the log is redirected to `output_log.txt` if there's no `output_log_path`.
+    pub output_log_to_stderr: bool,
+    /// How many Unity logs to keep, including the current one, `0` keeps all of them.
+    pub output_log_keep: usize,
+    /// Hides `--doorstop-*` arguments from Unity and the game, the original ones are kept in `DOORSTOP_ORIGINAL_ARGUMENTS`.
+    pub strip_arguments: bool,
+    /// Added to the end of the arguments Unity starts with.
+    pub extra_args: Vec<String>,
+    /// Added right after the program name to the arguments Unity starts with.
+    pub extra_args_prepend: Vec<String>,
+    /// Prevents the environment and command line from overriding `enabled` set by the config file.
+    pub ignore_disabled_env: bool,
+    pub target_assembly: Vec<PathBuf>,
+    /// The `[Target:<name>]` sections, see [`Config::all_targets`] for every assembly to invoke.
+    pub targets: Vec<Target>,
+    /// Static method invoked in the target assembly, as `Namespace.Type:Method`.
+    pub entrypoint: String,
+    /// Passed to the entrypoint as a `string[]` on Mono if it accepts one.
+    /// `CoreCLR` can't inspect the signature, so there the entrypoint is always called without arguments and has to read them with `doorstop_get_config` instead.
+    pub entrypoint_args: Vec<String>,
+    /// Invokes the entrypoints on their own thread instead of the one loading the runtime, so the game keeps starting up meanwhile.
+    pub entrypoint_async: bool,
+    /// Milliseconds the game waits for asynchronous entrypoints to return or call `doorstop_signal_ready`, `0` doesn't wait.
+    pub entrypoint_ready_timeout: usize,
+    /// Static method invoked in the target assemblies on exit, as `Namespace.Type:Method` or just `Method` on the entrypoint's type.
+    pub stop_method: Option<String>,
+    pub boot_config_override: Option<PathBuf>,
+    /// Native libraries loaded before any patches are applied, each gets a `doorstop_plugin_init` call.
+    pub native_plugins: Vec<PathBuf>,
+    /// Every library in this directory is loaded as a native plugin after `native_plugins`, in file name order.
+    pub native_plugins_dir: Option<PathBuf>,
+    pub mono_override: Option<PathBuf>,
+    pub mono_dll_search_path_override: Option<String>,
+    pub mono_debug_enabled: bool,
+    pub mono_debug_connect: bool,
+    pub mono_debug_suspend: bool,
+    pub mono_debug_address: Option<String>,
+    pub clr_runtime_coreclr_path: Option<PathBuf>,
+    pub clr_corlib_dir: Option<PathBuf>,
+    /// `RUST_LOG`-style filter, a default level and/or `target=level` directives separated by `,`.
+    pub log_level: Option<String>,
+    /// doorstop's own log, `doorstop.log` in the working directory if not set.
+    pub log_file: Option<PathBuf>,
+    pub log_file_enabled: bool,
+    /// Also writes the log as JSON lines to this file, same placeholders as `log_file`.
+    pub log_json_file: Option<PathBuf>,
+    /// How many doorstop logs to keep, including the current one.
+    pub log_keep: usize,
+    /// Stops writing the log file past this many bytes, `0` for no limit.
+    pub log_max_size: usize,
+    pub log_console_enabled: bool,
+    pub log_timestamps: bool,
+    pub log_thread_ids: bool,
+    /// Variables from the `[Environment]` section, set or unset before any patches are applied.
+    pub environment: Vec<EnvironmentVariable>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Legacy keys, variables and arguments that were used, always just a warning even in strict mode.
+    pub deprecations: Vec<Diagnostic>,
+    /// `--doorstop-*` arguments (lowercase) and the values they took, see [`Config::find_doorstop_arguments`].
+    pub used_arguments: Vec<(String, String)>,
+    /// Where each setting's value came from, keyed by `(section, key)`, settings left at their default aren't included.
+    pub sources: HashMap<(&'static str, &'static str), Source>,
+    /// Prints the effective config on startup, set by `--doorstop-print-config` or `DOORSTOP_PRINT_CONFIG=1`.
+    pub print: bool,
+    /// Exits right after printing the config, set by `--doorstop-print-config-and-exit` or `DOORSTOP_PRINT_CONFIG=exit`.
+    pub print_exit: bool,
+    /// Rewrites legacy keys in the config file, set by `--doorstop-migrate-config`.
+    pub migrate: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            file_path: None,
+            fragment_paths: Vec::new(),
+            working_dir: PathBuf::new(),
+            profile: None,
+            strict: false,
+            enabled: true,
+            ignore_disabled_env: false,
+            redirect_output_log: false,
+            output_log_path: None,
+            output_log_to_stderr: false,
+            output_log_keep: 0,
+            strip_arguments: false,
+            extra_args: Vec::new(),
+            extra_args_prepend: Vec::new(),
+            target_assembly: Vec::new(),
+            targets: Vec::new(),
+            entrypoint: "Doorstop.Entrypoint:Start".to_string(),
+            entrypoint_args: Vec::new(),
+            entrypoint_async: false,
+            entrypoint_ready_timeout: 0,
+            stop_method: None,
+            boot_config_override: None,
+            native_plugins: Vec::new(),
+            native_plugins_dir: None,
+            mono_override: None,
+            mono_dll_search_path_override: None,
+            mono_debug_enabled: false,
+            mono_debug_connect: false,
+            mono_debug_suspend: false,
+            mono_debug_address: Some("127.0.0.1:10000".to_string()),
+            clr_runtime_coreclr_path: None,
+            clr_corlib_dir: None,
+            log_level: None,
+            log_file: None,
+            log_file_enabled: true,
+            log_json_file: None,
+            log_keep: 1,
+            log_max_size: 0,
+            log_console_enabled: true,
+            log_timestamps: false,
+            log_thread_ids: false,
+            environment: Vec::new(),
+            diagnostics: Vec::new(),
+            deprecations: Vec::new(),
+            used_arguments: Vec::new(),
+            sources: HashMap::new(),
+            print: false,
+            print_exit: false,
+            migrate: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFailure {
+    Skip,
+    Fatal,
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    pub path: PathBuf,
+    /// Overrides the global `entrypoint` for this assembly.
+    pub entrypoint: Option<String>,
+    pub on_failure: OnFailure,
+}
+
+/// An environment variable changed by the `[Environment]` section, after every entry for it was applied.
+#[derive(Debug, Clone)]
+pub struct EnvironmentVariable {
+    pub name: String,
+    /// `None` if the variable is unset.
+    pub value: Option<String>,
+    /// The last entry that changed the variable.
+    pub source: Source,
+}
+
+/// A `[Profile:<name>]` section, kept until every file is loaded so the selected profile overrides all of them.
+struct Profile {
+    section: String,
+    name: String,
+    path: PathBuf,
+    /// `(key, value, line)` in the order they appear in the file.
+    entries: Vec<(String, String, Option<usize>)>,
+}
+
+impl Profile {
+    fn matches(&self, executable_name: &str) -> bool {
+        self.entries
+            .iter()
+            .find(|(key, _, _)| key == "match")
+            .is_some_and(|(_, patterns, _)| patterns.split(';').any(|pattern| glob_matches(pattern.trim(), executable_name)))
+    }
+}
+
+/// Where a config value was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    File { path: PathBuf, line: Option<usize> },
+    Environment(String),
+    CommandLine(String),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::File { path, line: Some(line) } => write!(f, "{}:{line}", path.display()),
+            Source::File { path, line: None } => write!(f, "{}", path.display()),
+            Source::Environment(name) => write!(f, "environment variable {name}"),
+            Source::CommandLine(name) => write!(f, "argument {name}"),
+        }
+    }
+}
+
+/// A problem found while loading the config, reported once logging is set up.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub source: Source,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.source, self.message)
+    }
+}
+
+fn parse_path(text: &str, base_dir: &Path, context: &LoadContext) -> Result<PathBuf, String> {
+    let text = expand_placeholders(text, context)?;
+    path::absolute(base_dir.join(&text)).map_err(|e| format!("invalid path `{text}`: {e}"))
+}
+
+enum Value<'a> {
+    Bool(&'a mut bool),
+    Count(&'a mut usize),
+    Text(&'a mut Option<String>),
+    /// Text that always has a value, like [`Value::Text`] with a default.
+    String(&'a mut String),
+    List(&'a mut Vec<String>),
+    PathList(&'a mut Vec<PathBuf>),
+    Path(&'a mut Option<PathBuf>),
+}
+
+impl Value<'_> {
+    /// Empty text leaves the value untouched, same as if it wasn't specified at all.
+    /// List items are separated by `;`.
+    fn parse(self, text: &str, base_dir: &Path, context: &LoadContext) -> Result<(), String> {
+        match self {
+            Value::Bool(value) => {
+                if !text.is_empty() {
+                    *value = match text.to_lowercase().as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(format!("invalid value `{text}`, expected `true` or `false`")),
+                    };
+                }
+            }
+            Value::Count(value) => {
+                if !text.is_empty() {
+                    *value = text.parse().map_err(|_| format!("invalid value `{text}`, expected a non-negative number"))?;
+                }
+            }
+            Value::Text(value) => {
+                if !text.is_empty() {
+                    *value = Some(expand_placeholders(text, context)?);
+                }
+            }
+            Value::String(value) => {
+                if !text.is_empty() {
+                    *value = expand_placeholders(text, context)?;
+                }
+            }
+            Value::List(value) => {
+                if !text.is_empty() {
+                    *value = text
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(|item| expand_placeholders(item, context))
+                        .collect::<Result<_, _>>()?;
+                }
+            }
+            Value::Path(value) => {
+                if !text.is_empty() {
+                    *value = Some(parse_path(text, base_dir, context)?);
+                }
+            }
+            Value::PathList(value) => {
+                if !text.is_empty() {
+                    *value = text
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(|item| parse_path(item, base_dir, context))
+                        .collect::<Result<_, _>>()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Setting {
+    pub section: &'static str,
+    pub key: &'static str,
+    pub env: Option<&'static str>,
+    pub arg: Option<&'static str>,
+    /// Written above the key by [`Config::to_ini`].
+    pub description: &'static str,
+    value: fn(&mut Config) -> Value<'_>,
+    text: fn(&Config) -> Option<String>,
+}
+
+impl Setting {
+    /// Name of the key inside of profile sections, keys from sections other than `[General]` are prefixed with their section name.
+    fn profile_key(&self) -> String {
+        if self.section == "General" {
+            self.key.to_string()
+        } else {
+            format!("{}.{}", self.section, self.key)
+        }
+    }
+}
+
+/// Escapes `$` so placeholders aren't expanded again when the text is parsed back.
+fn escape(text: &str) -> String {
+    text.replace('$', "$$")
+}
+
+/// Formats a value the same way it's written in config files, `None` if it's unset.
+trait ToText {
+    fn to_text(&self) -> Option<String>;
+}
+
+impl ToText for bool {
+    fn to_text(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl ToText for usize {
+    fn to_text(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl ToText for String {
+    fn to_text(&self) -> Option<String> {
+        Some(escape(self))
+    }
+}
+
+impl ToText for Option<String> {
+    fn to_text(&self) -> Option<String> {
+        self.as_deref().map(escape)
+    }
+}
+
+impl ToText for Vec<String> {
+    fn to_text(&self) -> Option<String> {
+        (!self.is_empty()).then(|| self.iter().map(|item| escape(item)).collect::<Vec<_>>().join(";"))
+    }
+}
+
+impl ToText for Vec<PathBuf> {
+    fn to_text(&self) -> Option<String> {
+        (!self.is_empty()).then(|| self.iter().map(|path| escape(&path.to_string_lossy())).collect::<Vec<_>>().join(";"))
+    }
+}
+
+impl ToText for Option<PathBuf> {
+    fn to_text(&self) -> Option<String> {
+        self.as_ref().map(|path| escape(&path.to_string_lossy()))
+    }
+}
+
+macro_rules! setting {
+    ($section:literal, $key:literal, $env:expr, $arg:expr, $kind:ident($field:ident), $description:literal) => {
+        Setting {
+            section: $section,
+            key: $key,
+            env: $env,
+            arg: $arg,
+            description: $description,
+            value: |config| Value::$kind(&mut config.$field),
+            text: |config| config.$field.to_text(),
+        }
+    };
+}
+
+#[rustfmt::skip]
+pub const SETTINGS: &[Setting] = &[
+    setting!("General", "strict", Some("DOORSTOP_STRICT"), Some("--doorstop-strict"), Bool(strict), "Fails on any config problem instead of just warning about it."),
+    setting!("General", "enabled", Some("DOORSTOP_ENABLED"), Some("--doorstop-enabled"), Bool(enabled), "Set to `false` to load the game without doorstop."),
+    setting!("General", "ignore_disable_switch", Some("DOORSTOP_IGNORE_DISABLED_ENV"), None, Bool(ignore_disabled_env), "Prevents the environment and command line from overriding `enabled`."),
+    setting!("General", "redirect_output_log", Some("DOORSTOP_REDIRECT_OUTPUT_LOG"), Some("--doorstop-redirect-output-log"), Bool(redirect_output_log), "Writes Unity's log to `output_log.txt` in the game directory."),
+    setting!("General", "output_log_path", Some("DOORSTOP_OUTPUT_LOG_PATH"), Some("--doorstop-output-log-path"), Path(output_log_path), "Where Unity writes its log instead, `{pid}`, `{timestamp}` and `{exe}` are replaced with the process id, start time and executable name."),
+    setting!("General", "output_log_to_stderr", Some("DOORSTOP_OUTPUT_LOG_TO_STDERR"), Some("--doorstop-output-log-to-stderr"), Bool(output_log_to_stderr), "Also copies Unity's log to stderr."),
+    setting!("General", "output_log_keep", Some("DOORSTOP_OUTPUT_LOG_KEEP"), Some("--doorstop-output-log-keep"), Count(output_log_keep), "How many Unity logs to keep, including the current one, `0` keeps all of them."),
+    setting!("General", "strip_arguments", Some("DOORSTOP_STRIP_ARGUMENTS"), Some("--doorstop-strip-arguments"), Bool(strip_arguments), "Hides `--doorstop-*` arguments from Unity and the game."),
+    setting!("General", "extra_args", Some("DOORSTOP_EXTRA_ARGS"), Some("--doorstop-extra-args"), List(extra_args), "Arguments added after the game's own ones, separated by `;`, e.g. `-screen-fullscreen;0`."),
+    setting!("General", "extra_args_prepend", Some("DOORSTOP_EXTRA_ARGS_PREPEND"), Some("--doorstop-extra-args-prepend"), List(extra_args_prepend), "Arguments added before the game's own ones, separated by `;`."),
+    setting!("General", "target_assembly", Some("DOORSTOP_TARGET_ASSEMBLY"), Some("--doorstop-target-assembly"), PathList(target_assembly), "Assemblies to load and invoke, separated by `;`."),
+    setting!("General", "entrypoint", Some("DOORSTOP_ENTRYPOINT"), Some("--doorstop-entrypoint"), String(entrypoint), "Static method invoked in the target assemblies, as `Namespace.Type:Method`."),
+    setting!("General", "entrypoint_args", Some("DOORSTOP_ENTRYPOINT_ARGS"), Some("--doorstop-entrypoint-args"), List(entrypoint_args), "Arguments passed to the entrypoint as a `string[]` on Mono, separated by `;`. CoreCLR calls the entrypoint without arguments, use `doorstop_get_config` there."),
+    setting!("General", "entrypoint_async", Some("DOORSTOP_ENTRYPOINT_ASYNC"), Some("--doorstop-entrypoint-async"), Bool(entrypoint_async), "Invokes the entrypoints on a separate thread instead of blocking the game's startup."),
+    setting!("General", "entrypoint_ready_timeout", Some("DOORSTOP_ENTRYPOINT_READY_TIMEOUT"), Some("--doorstop-entrypoint-ready-timeout"), Count(entrypoint_ready_timeout), "Milliseconds to wait for asynchronous entrypoints to return or call `doorstop_signal_ready` before letting the game continue, `0` doesn't wait."),
+    setting!("General", "stop_method", Some("DOORSTOP_STOP_METHOD"), Some("--doorstop-stop-method"), Text(stop_method), "Static method invoked in the target assemblies when the game exits, as `Namespace.Type:Method` or e.g. `Stop` for a method next to the entrypoint."),
+    setting!("General", "boot_config_override", Some("DOORSTOP_BOOT_CONFIG_OVERRIDE"), Some("--doorstop-boot-config-override"), Path(boot_config_override), "Used instead of the game's `boot.config`."),
+    setting!("General", "native_plugins", Some("DOORSTOP_NATIVE_PLUGINS"), Some("--doorstop-native-plugins"), PathList(native_plugins), "Native libraries loaded before any patches are applied, separated by `;`."),
+    setting!("General", "native_plugins_dir", Some("DOORSTOP_NATIVE_PLUGINS_DIR"), Some("--doorstop-native-plugins-dir"), Path(native_plugins_dir), "Directory of native libraries loaded after `native_plugins`, in file name order."),
+    setting!("UnityMono", "override", Some("DOORSTOP_MONO_OVERRIDE"), Some("--doorstop-mono-override"), Path(mono_override), "Mono runtime library used instead of the game's one."),
+    setting!("UnityMono", "dll_search_path_override", Some("DOORSTOP_MONO_DLL_SEARCH_PATH_OVERRIDE"), Some("--doorstop-mono-dll-search-path-override"), Text(mono_dll_search_path_override), "Directories searched for managed assemblies before the game's own ones."),
+    setting!("UnityMono", "debug_enabled", Some("DOORSTOP_MONO_DEBUG_ENABLED"), Some("--doorstop-mono-debug-enabled"), Bool(mono_debug_enabled), "Enables the Mono soft debugger."),
+    setting!("UnityMono", "debug_connect", Some("DOORSTOP_MONO_DEBUG_CONNECT"), Some("--doorstop-mono-debug-connect"), Bool(mono_debug_connect), "Connects to a debugger at `debug_address` instead of listening there."),
+    setting!("UnityMono", "debug_suspend", Some("DOORSTOP_MONO_DEBUG_SUSPEND"), Some("--doorstop-mono-debug-suspend"), Bool(mono_debug_suspend), "Waits for a debugger before running any managed code."),
+    setting!("UnityMono", "debug_address", Some("DOORSTOP_MONO_DEBUG_ADDRESS"), Some("--doorstop-mono-debug-address"), Text(mono_debug_address), "Address the debugger listens on or connects to."),
+    setting!("Il2Cpp", "coreclr_path", Some("DOORSTOP_CLR_RUNTIME_CORECLR_PATH"), Some("--doorstop-clr-runtime-coreclr-path"), Path(clr_runtime_coreclr_path), "CoreCLR runtime library used to run the target assemblies on IL2CPP."),
+    setting!("Il2Cpp", "corlib_dir", Some("DOORSTOP_CLR_CORLIB_DIR"), Some("--doorstop-clr-corlib-dir"), Path(clr_corlib_dir), "Directory of the base class library loaded by CoreCLR."),
+    setting!("Logging", "level", Some("DOORSTOP_LOG_LEVEL"), Some("--doorstop-log-level"), Text(log_level), "Lowest level logged, optionally followed by per-target levels like `RUST_LOG`, e.g. `info,doorstop_core::runtimes::mono=trace`."),
+    setting!("Logging", "file", Some("DOORSTOP_LOG_FILE"), Some("--doorstop-log-file"), Path(log_file), "Where doorstop writes its own log, `{pid}`, `{timestamp}` and `{exe}` are replaced with the process id, start time and executable name."),
+    setting!("Logging", "json_file", Some("DOORSTOP_LOG_JSON_FILE"), Some("--doorstop-log-json-file"), Path(log_json_file), "Also writes the log to this file as one JSON object per line, with the same placeholders as `file`."),
+    setting!("Logging", "keep", Some("DOORSTOP_LOG_KEEP"), Some("--doorstop-log-keep"), Count(log_keep), "How many doorstop logs to keep, including the current one. Older ones are renamed to `<file>.1`, `<file>.2` and so on, or deleted if the file name has `{pid}` or `{timestamp}` in it."),
+    setting!("Logging", "max_size", Some("DOORSTOP_LOG_MAX_SIZE"), Some("--doorstop-log-max-size"), Count(log_max_size), "Stops writing the log file once it reaches this many bytes, `0` for no limit."),
+    setting!("Logging", "file_enabled", Some("DOORSTOP_LOG_FILE_ENABLED"), Some("--doorstop-log-file-enabled"), Bool(log_file_enabled), "Set to `false` to not write a log file."),
+    setting!("Logging", "console_enabled", Some("DOORSTOP_LOG_CONSOLE_ENABLED"), Some("--doorstop-log-console-enabled"), Bool(log_console_enabled), "Set to `false` to not log to stderr."),
+    setting!("Logging", "timestamps", Some("DOORSTOP_LOG_TIMESTAMPS"), Some("--doorstop-log-timestamps"), Bool(log_timestamps), "Prefixes every line with the UTC time."),
+    setting!("Logging", "thread_ids", Some("DOORSTOP_LOG_THREAD_IDS"), Some("--doorstop-log-thread-ids"), Bool(log_thread_ids), "Prefixes every line with the id of the thread that logged it."),
+];
+
+/// A key from Doorstop v3 config files, mapped onto the current `(section, key)` (`None` if there's no equivalent anymore).
+struct LegacyKey {
+    section: &'static str,
+    key: &'static str,
+    replacement: Option<(&'static str, &'static str)>,
+}
+
+#[rustfmt::skip]
+const LEGACY_KEYS: &[LegacyKey] = &[
+    LegacyKey { section: "UnityDoorstop", key: "enabled", replacement: Some(("General", "enabled")) },
+    LegacyKey { section: "UnityDoorstop", key: "targetAssembly", replacement: Some(("General", "target_assembly")) },
+    LegacyKey { section: "UnityDoorstop", key: "redirectOutputLog", replacement: Some(("General", "redirect_output_log")) },
+    LegacyKey { section: "UnityDoorstop", key: "ignoreDisableSwitch", replacement: Some(("General", "ignore_disable_switch")) },
+    LegacyKey { section: "UnityDoorstop", key: "dllSearchPathOverride", replacement: Some(("UnityMono", "dll_search_path_override")) },
+    LegacyKey { section: "MonoBackend", key: "runtimeLib", replacement: Some(("UnityMono", "override")) },
+    LegacyKey { section: "MonoBackend", key: "configDir", replacement: None },
+    LegacyKey { section: "MonoBackend", key: "corlibDir", replacement: None },
+    LegacyKey { section: "MonoBackend", key: "debugEnabled", replacement: Some(("UnityMono", "debug_enabled")) },
+    LegacyKey { section: "MonoBackend", key: "debugSuspend", replacement: Some(("UnityMono", "debug_suspend")) },
+    LegacyKey { section: "MonoBackend", key: "debugAddress", replacement: Some(("UnityMono", "debug_address")) },
+];
+
+/// Environment variables from Doorstop v3, mapped onto the current ones.
+const LEGACY_ENVIRONMENT_VARIABLES: &[(&str, &str)] = &[
+    ("DOORSTOP_ENABLE", "DOORSTOP_ENABLED"),
+    ("DOORSTOP_INVOKE_DLL_PATH", "DOORSTOP_TARGET_ASSEMBLY"),
+];
+
+/// Arguments from Doorstop v3, mapped onto the current ones.
+const LEGACY_ARGUMENTS: &[(&str, &str)] = &[
+    ("--doorstop-enable", "--doorstop-enabled"),
+    ("--doorstop-target", "--doorstop-target-assembly"),
+    ("--doorstop-dll-search-override", "--doorstop-mono-dll-search-path-override"),
+    ("--mono-debug-enabled", "--doorstop-mono-debug-enabled"),
+    ("--mono-debug-suspend", "--doorstop-mono-debug-suspend"),
+    ("--mono-debug-address", "--doorstop-mono-debug-address"),
+];
+
+fn find_setting(section: &str, key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|setting| setting.section == section && setting.key == key)
+}
+
+fn find_legacy_key(section: &str, key: &str) -> Option<&'static LegacyKey> {
+    LEGACY_KEYS.iter().find(|legacy_key| legacy_key.section == section && legacy_key.key == key)
+}
+
+/// `DOORSTOP_*` variables that aren't settings, either read elsewhere or set by doorstop itself for managed code and child processes.
+const OTHER_ENVIRONMENT_VARIABLES: &[&str] = &[
+    "DOORSTOP_CONFIG",
+    "DOORSTOP_CONFIG_PATH",
+    "DOORSTOP_PROFILE",
+    "DOORSTOP_PRINT_CONFIG",
+    "DOORSTOP_ORIGINAL_ARGUMENTS",
+    "DOORSTOP_ATTACH_CONSOLE",
+    "DOORSTOP_INITIALIZED",
+    "DOORSTOP_PROCESS_PATH",
+    "DOORSTOP_INVOKE_DLL_PATH",
+    "DOORSTOP_INVOKE_DLL_PATHS",
+    "DOORSTOP_MANAGED_FOLDER_DIR",
+    "DOORSTOP_DLL_SEARCH_DIRS",
+    "DOORSTOP_PLAYER",
+    "DOORSTOP_PLAYER_EXECUTABLE",
+    "DOORSTOP_PATH",
+    "DOORSTOP_X64_PATH",
+    "DOORSTOP_X86_PATH",
+    "DOORSTOP_ARM64_PATH",
+    "DOORSTOP_ARM_PATH",
+];
+
+/// `--doorstop-*` arguments that aren't settings, but still take a value.
+const OTHER_ARGUMENTS: &[&str] = &["--doorstop-config", "--doorstop-profile"];
+
+/// `--doorstop-*` arguments that aren't settings and don't take a value.
+const OTHER_FLAGS: &[&str] = &[
+    "--doorstop-print-config",
+    "--doorstop-print-config-and-exit",
+    "--doorstop-disable",
+    "--doorstop-migrate-config",
+];
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    let name = name.to_lowercase();
+
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, candidate)| *distance <= 2.max(candidate.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .map_or_else(String::new, |(_, candidate)| format!(", did you mean `{candidate}`?"))
+}
+
+/// Looks up the value of an environment variable, used instead of the process' environment while loading the `[Environment]` section.
+type Variables<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn resolve_placeholder(name: &str, context: &LoadContext, variables: Option<Variables>) -> Result<String, String> {
+    let var = |variable: &str| {
+        match variables {
+            Some(variables) => variables(variable),
+            None => context.var(variable).map(ToString::to_string),
+        }
+        .ok_or_else(|| format!("environment variable `{variable}` is not set"))
+    };
+
+    if let Some(variable) = name.strip_prefix("ENV:") {
+        return var(variable);
+    }
+
+    match name {
+        "GAME_DIR" => Ok(context.game_dir.to_string_lossy().into_owned()),
+        "DATA_DIR" => context
+            .data_dir
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned())
+            .ok_or_else(|| "couldn't find the game's data directory".to_string()),
+        "EXE_NAME" => Ok(context.executable_name.clone()),
+        _ if variables.is_some() => var(name),
+        _ => {
+            let suggestion = suggest(name, ["GAME_DIR", "DATA_DIR", "EXE_NAME"]);
+            Err(format!("unknown placeholder `${{{name}}}`{suggestion}"))
+        }
+    }
+}
+
+/// Expands `${GAME_DIR}`, `${DATA_DIR}`, `${EXE_NAME}` and `${ENV:NAME}` placeholders and a leading `~`, `$$` is a literal `$`.
+fn expand_placeholders(text: &str, context: &LoadContext) -> Result<String, String> {
+    expand_placeholders_with(text, context, None)
+}
+
+/// Same as [`expand_placeholders`], but `variables` are used for `${ENV:NAME}` and any `${NAME}` that isn't one of the other placeholders.
+fn expand_placeholders_with(text: &str, context: &LoadContext, variables: Option<Variables>) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    if let Some(stripped) = rest.strip_prefix('~')
+        && (stripped.is_empty() || stripped.starts_with(['/', '\\']))
+    {
+        let home_dir = context.home_dir.as_ref().ok_or_else(|| "couldn't find the home directory".to_string())?;
+        result.push_str(&home_dir.to_string_lossy());
+        rest = stripped;
+    }
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(stripped) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("${") {
+            let Some(end) = stripped.find('}') else {
+                return Err(format!("unterminated placeholder in `{text}`"));
+            };
+
+            result.push_str(&resolve_placeholder(&stripped[..end], context, variables)?);
+            rest = &stripped[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Matches `text` against a pattern with `*` and `?` wildcards, ignoring ASCII case.
+#[must_use]
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_ascii_lowercase().chars().collect();
+    let text: Vec<char> = text.to_ascii_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => {
+                let Some((star, star_t)) = backtrack else {
+                    return false;
+                };
+
+                backtrack = Some((star, star_t + 1));
+                p = star + 1;
+                t = star_t + 1;
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Returns the value of a `--doorstop-*` argument or its environment variable, for the ones that need to be known before loading the config file.
+fn find_override(context: &LoadContext, arg: &str, env_name: &str) -> Option<(String, Source)> {
+    let mut args = context.args.iter();
+    let mut value = None;
+    while let Some(name) = args.next() {
+        if name.eq_ignore_ascii_case(arg) {
+            value = args.next();
+        }
+    }
+
+    if let Some(value) = value.filter(|value| !value.is_empty()) {
+        return Some((value.clone(), Source::CommandLine(arg.to_string())));
+    }
+
+    context
+        .var(env_name)
+        .filter(|value| !value.is_empty())
+        .map(|value| (value.to_string(), Source::Environment(env_name.to_string())))
+}
+
+/// Returns whether a `--doorstop-*` flag is passed or its environment variable is set to anything other than `0` or `false`.
+fn find_flag(context: &LoadContext, arg: &str, env_name: &str) -> bool {
+    context.has_arg(arg)
+        || context
+            .var(env_name)
+            .is_some_and(|value| !matches!(value.to_lowercase().as_str(), "" | "0" | "false"))
+}
+
+/// Maps every `(section, key)` to the lines it appears on, since rust-ini doesn't keep track of them.
+fn index_lines(text: &str) -> HashMap<(Option<&str>, &str), VecDeque<usize>> {
+    let mut lines: HashMap<_, VecDeque<_>> = HashMap::new();
+    let mut section = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with([';', '#']) {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = Some(name.trim());
+            continue;
+        }
+
+        if let Some(separator) = line.find(['=', ':']) {
+            lines.entry((section, line[..separator].trim())).or_default().push_back(i + 1);
+        }
+    }
+
+    lines
+}
+
+fn find_section_line(text: &str, section: &str) -> Option<usize> {
+    text.lines().position(|line| line.trim() == format!("[{section}]")).map(|i| i + 1)
+}
+
+fn get_user_config_dir(context: &LoadContext) -> Option<PathBuf> {
+    cfg_if! {
+        if #[cfg(windows)] {
+            context.var("APPDATA").map(PathBuf::from)
+        } else if #[cfg(target_os = "macos")] {
+            context.home_dir.as_ref().map(|home_dir| home_dir.join("Library/Application Support"))
+        } else {
+            context
+                .var("XDG_CONFIG_HOME")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| context.home_dir.as_ref().map(|home_dir| home_dir.join(".config")))
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config, each step overriding the previous ones:
+    /// 1. files from `include` keys, then the config file itself
+    /// 2. drop-ins from `doorstop_config.d`, in file name order
+    /// 3. the selected profile, from any of those files
+    /// 4. environment variables
+    /// 5. command line arguments
+    ///
+    /// `--doorstop-disable` and the `doorstop_disabled` marker file are checked last.
+    pub fn load(context: &LoadContext) -> anyhow::Result<Config> {
+        let print_exit =
+            context.has_arg("--doorstop-print-config-and-exit") || context.var("DOORSTOP_PRINT_CONFIG").is_some_and(|value| value.eq_ignore_ascii_case("exit"));
+
+        let mut config = Config {
+            file_path: Self::find_config_file(context)?,
+            working_dir: context.working_dir.clone(),
+            print: print_exit || find_flag(context, "--doorstop-print-config", "DOORSTOP_PRINT_CONFIG"),
+            print_exit,
+            migrate: context.has_arg("--doorstop-migrate-config"),
+            ..Config::default()
+        };
+
+        let mut profiles = Vec::new();
+
+        if let Some(file_path) = config.file_path.clone() {
+            config.load_from_file(context, &file_path, &mut profiles, &mut Vec::new());
+        }
+
+        // Drop-ins live next to the config file, e.g. `doorstop_config.d/*.ini`
+        let drop_in_dir = match config.file_path.as_ref() {
+            Some(file_path) => file_path.with_extension("d"),
+            None => context.game_dir.join(Path::new(CONFIG_FILE_NAME).with_extension("d")),
+        };
+
+        if let Ok(entries) = fs::read_dir(&drop_in_dir) {
+            let mut drop_ins: Vec<PathBuf> = entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ini")))
+                .collect();
+            drop_ins.sort();
+
+            for drop_in in drop_ins {
+                config.fragment_paths.push(drop_in.clone());
+                config.load_from_file(context, &drop_in, &mut profiles, &mut Vec::new());
+            }
+        }
+
+        let explicit_profile = find_override(context, "--doorstop-profile", "DOORSTOP_PROFILE");
+        config.apply_profile(context, &profiles, explicit_profile);
+
+        config.load_from_environment(context);
+        config.load_from_command_line(context);
+
+        if !config.ignore_disabled_env && context.has_arg("--doorstop-disable") {
+            config.enabled = false;
+            config
+                .sources
+                .insert(("General", "enabled"), Source::CommandLine("--doorstop-disable".to_string()));
+        }
+
+        let marker_path = context.game_dir.join(DISABLED_MARKER_FILE_NAME);
+        if marker_path.exists() {
+            config.enabled = false;
+            config.sources.insert(("General", "enabled"), Source::File { path: marker_path, line: None });
+        }
+
+        if context.var("MONO_ARGUMENTS").is_some() {
+            config.mono_debug_enabled = true;
+            config
+                .sources
+                .insert(("UnityMono", "debug_enabled"), Source::Environment("MONO_ARGUMENTS".to_string()));
+        }
+
+        Ok(config)
+    }
+
+    /// Every assembly to load and invoke, in order, `target_assembly` ones first and then the `[Target:<name>]` sections.
+    pub fn all_targets(&self) -> impl Iterator<Item = Target> + '_ {
+        let target_assemblies = self.target_assembly.iter().map(|path| Target {
+            name: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            path: path.clone(),
+            entrypoint: None,
+            on_failure: OnFailure::Fatal,
+        });
+
+        target_assemblies.chain(self.targets.iter().cloned())
+    }
+
+    /// Whether the arguments Unity starts with have to be changed, either by `strip_arguments` or the extra arguments.
+    #[must_use]
+    pub fn rewrites_arguments(&self) -> bool {
+        self.strip_arguments || !self.extra_args.is_empty() || !self.extra_args_prepend.is_empty()
+    }
+
+    /// Marks the known `--doorstop-*` arguments in `args`, along with the values they took while loading, unknown ones are left alone.
+    /// A value that was rejected isn't marked, since it might be an unrelated argument.
+    #[must_use]
+    pub fn find_doorstop_arguments(&self, args: &[String]) -> Vec<bool> {
+        let mut result = vec![false; args.len()];
+
+        let mut i = 0;
+        while i < args.len() {
+            let name = args[i].to_lowercase();
+
+            if OTHER_FLAGS.contains(&name.as_str()) {
+                result[i] = true;
+            } else if OTHER_ARGUMENTS.contains(&name.as_str()) {
+                result[i] = true;
+                if let Some(value) = result.get_mut(i + 1) {
+                    *value = true;
+                    i += 1;
+                }
+            } else if SETTINGS.iter().any(|setting| setting.arg == Some(name.as_str())) || LEGACY_ARGUMENTS.iter().any(|(legacy_name, _)| *legacy_name == name)
+            {
+                result[i] = true;
+                if let Some(value) = args.get(i + 1)
+                    && self
+                        .used_arguments
+                        .iter()
+                        .any(|(used_name, used_value)| *used_name == name && used_value == value)
+                {
+                    result[i + 1] = true;
+                    i += 1;
+                }
+            }
+
+            i += 1;
+        }
+
+        result
+    }
+
+    /// Logs every problem found while loading, or fails if strict mode is enabled.
+    /// Deprecations are only logged, a working legacy config shouldn't stop the game.
+    pub fn report_diagnostics(&self) -> anyhow::Result<()> {
+        for deprecation in &self.deprecations {
+            warn!("{deprecation}");
+        }
+
+        if self.diagnostics.is_empty() {
+            return Ok(());
+        }
+
+        if self.strict {
+            let diagnostics: Vec<String> = self.diagnostics.iter().map(ToString::to_string).collect();
+            bail!("Invalid config (strict mode is enabled):\n{}", diagnostics.join("\n"));
+        }
+
+        for diagnostic in &self.diagnostics {
+            warn!("{diagnostic}");
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn describe_source(&self, section: &str, key: &str) -> String {
+        self.sources.get(&(section, key)).map_or_else(|| "default".to_string(), ToString::to_string)
+    }
+
+    /// Formats a setting's value the same way it's written in config files, `None` if it's unset or there's no such setting.
+    #[must_use]
+    pub fn get(&self, section: &str, key: &str) -> Option<String> {
+        find_setting(section, key).and_then(|setting| (setting.text)(self))
+    }
+
+    /// Formats every setting's final value along with where it came from.
+    #[must_use]
+    pub fn dump(&self) -> String {
+        let mut lines = vec![format!(
+            "Effective config (file: {}, profile: {}):",
+            self.file_path.as_ref().map_or_else(|| "<none>".to_string(), |path| path.display().to_string()),
+            self.profile.as_deref().unwrap_or("<none>"),
+        )];
+
+        for setting in SETTINGS {
+            let source = self.describe_source(setting.section, setting.key);
+            let value = (setting.text)(self).unwrap_or_else(|| "<unset>".to_string());
+            lines.push(format!("  [{}] {} = {value} ({source})", setting.section, setting.key));
+        }
+
+        for environment in &self.environment {
+            let value = environment.value.as_deref().map_or_else(|| "<unset>".to_string(), |value| format!("`{value}`"));
+            lines.push(format!("  environment {} = {value} ({})", environment.name, environment.source));
+        }
+
+        for target in self.all_targets() {
+            lines.push(format!(
+                "  target {} = {} (entrypoint: {}, on_failure: {:?})",
+                target.name,
+                target.path.display(),
+                target.entrypoint.as_ref().unwrap_or(&self.entrypoint),
+                target.on_failure,
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    fn report(&mut self, source: Source, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            source,
+            message: message.into(),
+        });
+    }
+
+    fn deprecate(&mut self, source: Source, message: impl Into<String>) {
+        self.deprecations.push(Diagnostic {
+            source,
+            message: message.into(),
+        });
+    }
+
+    /// Whether the environment and command line are allowed to change this setting.
+    fn can_override(&self, setting: &Setting) -> bool {
+        !(self.ignore_disabled_env && setting.section == "General" && setting.key == "enabled")
+    }
+
+    /// Parses `text` into a setting and records `source` as where it came from, empty text leaves the setting untouched.
+    /// Relative paths are resolved against the directory of a file source, or the working directory otherwise.
+    pub fn set(&mut self, context: &LoadContext, setting: &Setting, text: &str, source: &Source) -> Result<(), String> {
+        let base_dir = match source {
+            Source::File { path, .. } => path.parent().unwrap().to_path_buf(),
+            Source::Environment(_) | Source::CommandLine(_) => self.working_dir.clone(),
+        };
+
+        (setting.value)(self).parse(text, &base_dir, context)?;
+
+        if !text.is_empty() {
+            self.sources.insert((setting.section, setting.key), source.clone());
+        }
+
+        Ok(())
+    }
+
+    fn apply(&mut self, context: &LoadContext, setting: &Setting, text: &str, source: Source) {
+        if let Err(message) = self.set(context, setting, text, &source) {
+            self.report(source, message);
+        }
+    }
+
+    /// Looks for the config file in the following order:
+    /// 1. `--doorstop-config <path>` argument
+    /// 2. `DOORSTOP_CONFIG` environment variable
+    /// 3. `doorstop_config.ini` in the game directory
+    /// 4. `doorstop/<executable name>.ini` in the per-user config directory
+    ///
+    /// The command line takes precedence over the environment, same as for every other setting.
+    fn find_config_file(context: &LoadContext) -> anyhow::Result<Option<PathBuf>> {
+        if let Some((path, _)) = find_override(context, "--doorstop-config", "DOORSTOP_CONFIG") {
+            let path = path::absolute(context.working_dir.join(path))?;
+            if !path.is_file() {
+                bail!("Config file {} doesn't exist", path.display());
+            }
+
+            return Ok(Some(path));
+        }
+
+        let mut candidates = vec![context.game_dir.join(CONFIG_FILE_NAME)];
+
+        if let Some(user_config_dir) = get_user_config_dir(context) {
+            candidates.push(user_config_dir.join("doorstop").join(format!("{}.ini", context.executable_name)));
+        }
+
+        Ok(candidates.into_iter().find(|path| path.is_file()))
+    }
+
+    /// Loads the `[General]`, `[UnityMono]` and `[Il2Cpp]` sections, `[Profile:<name>]` sections are added to `profiles` for [`Config::apply_profile`].
+    ///
+    /// Files from `include` keys in `[General]` are loaded first, so the including file overrides them.
+    fn load_from_file(&mut self, context: &LoadContext, path: &Path, profiles: &mut Vec<Profile>, include_stack: &mut Vec<PathBuf>) {
+        let source = |line| Source::File {
+            path: path.to_path_buf(),
+            line,
+        };
+
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.report(source(None), format!("failed to read config file: {e}"));
+                return;
+            }
+        };
+
+        let file = match Ini::load_from_str_noescape(&text) {
+            Ok(file) => file,
+            Err(e) => {
+                self.report(source(Some(e.line)), format!("failed to parse config file: {}", e.msg));
+                return;
+            }
+        };
+
+        let mut lines = index_lines(&text);
+
+        if let Some(general) = file.section(Some("General")) {
+            let include_lines = lines.get(&(Some("General"), "include")).cloned().unwrap_or_default();
+
+            include_stack.push(path.to_path_buf());
+
+            for (i, value) in general.get_all("include").enumerate() {
+                let source = source(include_lines.get(i).copied());
+
+                let include_path = match parse_path(value, path.parent().unwrap(), context) {
+                    Ok(include_path) => include_path,
+                    Err(message) => {
+                        self.report(source, message);
+                        continue;
+                    }
+                };
+
+                if include_stack.contains(&include_path) {
+                    self.report(source, format!("{} is already being included", include_path.display()));
+                } else if !include_path.is_file() {
+                    self.report(source, format!("included file {} doesn't exist", include_path.display()));
+                } else {
+                    self.fragment_paths.push(include_path.clone());
+                    self.load_from_file(context, &include_path, profiles, include_stack);
+                }
+            }
+
+            include_stack.pop();
+        }
+
+        for (section, properties) in &file {
+            if let Some(name) = section.and_then(|section| section.strip_prefix(PROFILE_SECTION_PREFIX)) {
+                let entries = properties
+                    .iter()
+                    .map(|(key, value)| {
+                        let line = lines.get_mut(&(section, key)).and_then(VecDeque::pop_front);
+                        (key.to_string(), value.to_string(), line)
+                    })
+                    .collect();
+
+                profiles.push(Profile {
+                    section: section.unwrap().to_string(),
+                    name: name.trim().to_string(),
+                    path: path.to_path_buf(),
+                    entries,
+                });
+                continue;
+            }
+
+            if let Some(name) = section.and_then(|section| section.strip_prefix(TARGET_SECTION_PREFIX)) {
+                self.load_target(context, path, &text, section.unwrap(), name.trim(), properties, &mut lines);
+                continue;
+            }
+
+            if section == Some(ENVIRONMENT_SECTION) {
+                for (key, value) in properties {
+                    let line = lines.get_mut(&(section, key)).and_then(VecDeque::pop_front);
+                    self.apply_environment(context, key, value, source(line));
+                }
+
+                continue;
+            }
+
+            if let Some(section) = section
+                && LEGACY_KEYS.iter().any(|legacy_key| legacy_key.section == section)
+            {
+                for (key, value) in properties {
+                    let line = lines.get_mut(&(Some(section), key)).and_then(VecDeque::pop_front);
+
+                    match find_legacy_key(section, key) {
+                        Some(LegacyKey {
+                            replacement: Some((new_section, new_key)),
+                            ..
+                        }) => {
+                            self.deprecate(
+                                source(line),
+                                format!("`[{section}] {key}` is deprecated, use `[{new_section}] {new_key}` instead or run with --doorstop-migrate-config"),
+                            );
+                            self.apply(context, find_setting(new_section, new_key).unwrap(), value, source(line));
+                        }
+                        Some(LegacyKey { replacement: None, .. }) => {
+                            if !value.is_empty() {
+                                self.deprecate(source(line), format!("`[{section}] {key}` is no longer supported"));
+                            }
+                        }
+                        None => {
+                            let legacy_keys = LEGACY_KEYS.iter().filter(|legacy_key| legacy_key.section == section);
+                            let suggestion = suggest(key, legacy_keys.map(|legacy_key| legacy_key.key));
+                            self.report(source(line), format!("unknown key `{key}` in section `[{section}]`{suggestion}"));
+                        }
+                    }
+                }
+
+                continue;
+            }
+
+            if let Some(section) = section
+                && !SETTINGS.iter().any(|setting| setting.section == section)
+            {
+                let line = find_section_line(&text, section);
+                let suggestion = suggest(section, SETTINGS.iter().map(|setting| setting.section).chain([ENVIRONMENT_SECTION]));
+                self.report(source(line), format!("unknown section `[{section}]`{suggestion}"));
+                continue;
+            }
+
+            for (key, value) in properties {
+                let line = lines.get_mut(&(section, key)).and_then(VecDeque::pop_front);
+
+                let Some(section) = section else {
+                    self.report(source(line), format!("key `{key}` has to be in a section"));
+                    continue;
+                };
+
+                if section == "General" && key == "include" {
+                    continue;
+                }
+
+                if let Some(setting) = find_setting(section, key) {
+                    self.apply(context, setting, value, source(line));
+                } else {
+                    let suggestion = suggest(key, SETTINGS.iter().filter(|setting| setting.section == section).map(|setting| setting.key));
+                    self.report(source(line), format!("unknown key `{key}` in section `[{section}]`{suggestion}"));
+                }
+            }
+        }
+    }
+
+    /// Applies the selected profile on top of every loaded file, the environment and command line still override it.
+    /// Unless one is selected explicitly, the first profile whose `match` glob matches the executable name is used.
+    /// A profile with sections in several files gets all of them applied, in the order the files were loaded.
+    fn apply_profile(&mut self, context: &LoadContext, profiles: &[Profile], explicit_profile: Option<(String, Source)>) {
+        let selected = match &explicit_profile {
+            Some((name, _)) => profiles.iter().find(|profile| profile.name == *name),
+            None => profiles.iter().find(|profile| profile.matches(&context.executable_name)),
+        };
+        self.profile = selected.map(|profile| profile.name.clone());
+
+        if let Some((name, source)) = explicit_profile
+            && self.profile.is_none()
+        {
+            self.report(source, format!("profile `{name}` doesn't exist"));
+        }
+
+        let profile_keys: Vec<String> = SETTINGS.iter().map(Setting::profile_key).collect();
+
+        // Every profile is validated, but only the selected one is applied
+        for profile in profiles {
+            let is_selected = self.profile.as_ref() == Some(&profile.name);
+
+            for (key, value, line) in &profile.entries {
+                let source = Source::File {
+                    path: profile.path.clone(),
+                    line: *line,
+                };
+
+                if key == "match" {
+                    continue;
+                }
+
+                if let Some(key) = key.strip_prefix(ENVIRONMENT_SECTION).and_then(|key| key.strip_prefix('.')) {
+                    if is_selected {
+                        self.apply_environment(context, key, value, source);
+                    }
+
+                    continue;
+                }
+
+                if let Some(index) = profile_keys.iter().position(|profile_key| profile_key == key) {
+                    if is_selected {
+                        self.apply(context, &SETTINGS[index], value, source);
+                    }
+                } else {
+                    let suggestion = suggest(key, profile_keys.iter().map(String::as_str));
+                    self.report(source, format!("unknown key `{key}` in section `[{}]`{suggestion}", profile.section));
+                }
+            }
+        }
+    }
+
+    /// Applies an `[Environment]` entry on top of the previous ones and the process' environment.
+    /// `NAME = value` sets the variable, `NAME += value` and `NAME ^= value` append and prepend to it (separated by the platform's path separator) and `NAME -=` unsets it.
+    /// Besides the usual placeholders, values can refer to any environment variable as `${NAME}`.
+    fn apply_environment(&mut self, context: &LoadContext, key: &str, text: &str, source: Source) {
+        let (name, operator) = match key.strip_suffix(['+', '^', '-']) {
+            Some(name) => (name.trim_end(), key.chars().last()),
+            None => (key, None),
+        };
+
+        if name.is_empty() {
+            self.report(source, format!("missing variable name in `{key}`"));
+            return;
+        }
+
+        let variables = |variable: &str| match self.environment.iter().find(|environment| variable_name_eq(&environment.name, variable)) {
+            Some(environment) => environment.value.clone(),
+            None => context.var(variable).map(ToString::to_string),
+        };
+
+        let value = if operator == Some('-') {
+            if !text.is_empty() {
+                self.report(source, format!("`{name} -=` unsets the variable and doesn't take a value"));
+                return;
+            }
+
+            None
+        } else {
+            let current = variables(name).filter(|current| !current.is_empty());
+
+            let text = match expand_placeholders_with(text, context, Some(&variables)) {
+                Ok(text) => text,
+                Err(message) => {
+                    self.report(source, message);
+                    return;
+                }
+            };
+
+            Some(match (operator, current) {
+                (Some('+'), Some(current)) => format!("{current}{PATH_LIST_SEPARATOR}{text}"),
+                (Some('^'), Some(current)) => format!("{text}{PATH_LIST_SEPARATOR}{current}"),
+                _ => text,
+            })
+        };
+
+        if let Some(environment) = self.environment.iter_mut().find(|environment| variable_name_eq(&environment.name, name)) {
+            environment.value = value;
+            environment.source = source;
+        } else {
+            self.environment.push(EnvironmentVariable {
+                name: name.to_string(),
+                value,
+                source,
+            });
+        }
+    }
+
+    /// Loads a `[Target:<name>]` section, with a required `path` and optional `entrypoint` and `on_failure` (`skip` or `fatal`) keys.
+    #[allow(clippy::too_many_arguments)]
+    fn load_target<'a>(
+        &mut self,
+        context: &LoadContext,
+        path: &Path,
+        text: &str,
+        section: &'a str,
+        name: &str,
+        properties: &'a Properties,
+        lines: &mut HashMap<(Option<&'a str>, &'a str), VecDeque<usize>>,
+    ) {
+        let source = |line| Source::File {
+            path: path.to_path_buf(),
+            line,
+        };
+
+        let mut target_path = None;
+        let mut entrypoint = None;
+        let mut on_failure = OnFailure::Fatal;
+
+        for (key, value) in properties {
+            let line = lines.get_mut(&(Some(section), key)).and_then(VecDeque::pop_front);
+
+            let result = match key {
+                "path" => Value::Path(&mut target_path).parse(value, path.parent().unwrap(), context),
+                "entrypoint" => Value::Text(&mut entrypoint).parse(value, path.parent().unwrap(), context),
+                "on_failure" => match value.to_lowercase().as_str() {
+                    "skip" => {
+                        on_failure = OnFailure::Skip;
+                        Ok(())
+                    }
+                    "fatal" => {
+                        on_failure = OnFailure::Fatal;
+                        Ok(())
+                    }
+                    _ => Err(format!("invalid value `{value}`, expected `skip` or `fatal`")),
+                },
+                _ => {
+                    let suggestion = suggest(key, ["path", "entrypoint", "on_failure"]);
+                    Err(format!("unknown key `{key}` in section `[{section}]`{suggestion}"))
+                }
+            };
+
+            if let Err(message) = result {
+                self.report(source(line), message);
+            }
+        }
+
+        let Some(target_path) = target_path else {
+            let line = find_section_line(text, section);
+            self.report(source(line), format!("section `[{section}]` is missing `path`"));
+            return;
+        };
+
+        self.targets.push(Target {
+            name: name.to_string(),
+            path: target_path,
+            entrypoint,
+            on_failure,
+        });
+    }
+
+    fn load_from_environment(&mut self, context: &LoadContext) {
+        // Resolved before anything else, so it already applies to the variables below
+        let ignore_disable_switch = find_setting("General", "ignore_disable_switch").unwrap();
+        if let Some(name) = ignore_disable_switch.env
+            && let Some(value) = context.var(name)
+        {
+            self.apply(context, ignore_disable_switch, value, Source::Environment(name.to_string()));
+        }
+
+        // Applied first so the current names take precedence
+        for (legacy_name, name) in LEGACY_ENVIRONMENT_VARIABLES {
+            // Doorstop itself sets DOORSTOP_INVOKE_DLL_PATH, child processes inherit it
+            if *legacy_name == "DOORSTOP_INVOKE_DLL_PATH" && context.var("DOORSTOP_INITIALIZED").is_some() {
+                continue;
+            }
+
+            if let Some(value) = context.var(legacy_name) {
+                let setting = SETTINGS.iter().find(|setting| setting.env == Some(*name)).unwrap();
+                if self.can_override(setting) {
+                    let source = Source::Environment((*legacy_name).to_string());
+                    self.deprecate(source.clone(), format!("deprecated environment variable, use {name} instead"));
+                    self.apply(context, setting, value, source);
+                }
+            }
+        }
+
+        for setting in SETTINGS {
+            if let Some(name) = setting.env
+                && let Some(value) = context.var(name)
+                && self.can_override(setting)
+                && !ptr::eq(setting, ignore_disable_switch)
+            {
+                self.apply(context, setting, value, Source::Environment(name.to_string()));
+            }
+        }
+
+        for (name, _) in &context.env {
+            let name = name.as_str();
+
+            if name.starts_with("DOORSTOP_")
+                && !OTHER_ENVIRONMENT_VARIABLES.contains(&name)
+                && !LEGACY_ENVIRONMENT_VARIABLES.iter().any(|(legacy_name, _)| *legacy_name == name)
+                && !SETTINGS.iter().any(|setting| setting.env == Some(name))
+            {
+                let suggestion = suggest(name, SETTINGS.iter().filter_map(|setting| setting.env));
+                self.report(Source::Environment(name.to_string()), format!("unknown environment variable{suggestion}"));
+            }
+        }
+    }
+
+    fn load_from_command_line(&mut self, context: &LoadContext) {
+        let mut args = context.args.iter().peekable();
+        while let Some(arg) = args.next() {
+            let arg = arg.to_lowercase();
+            let name = arg.clone();
+            let source = Source::CommandLine(name.clone());
+
+            let name = match LEGACY_ARGUMENTS.iter().find(|(legacy_name, _)| *legacy_name == name) {
+                Some((_, new_name)) => {
+                    self.deprecate(source.clone(), format!("deprecated argument, use {new_name} instead"));
+                    (*new_name).to_string()
+                }
+                None => name,
+            };
+
+            if !name.starts_with("--doorstop-") {
+                continue;
+            }
+
+            if OTHER_FLAGS.contains(&name.as_str()) {
+                continue;
+            }
+
+            if OTHER_ARGUMENTS.contains(&name.as_str()) {
+                args.next();
+                continue;
+            }
+
+            let Some(setting) = SETTINGS.iter().find(|setting| setting.arg == Some(name.as_str())) else {
+                let suggestion = suggest(&name, SETTINGS.iter().filter_map(|setting| setting.arg));
+                self.report(source, format!("unknown argument{suggestion}"));
+                continue;
+            };
+
+            let Some(value) = args.peek() else {
+                self.report(source, "missing value");
+                continue;
+            };
+
+            if !self.can_override(setting) {
+                self.used_arguments.push((arg, (*value).clone()));
+                args.next();
+                continue;
+            }
+
+            match self.set(context, setting, value, &source) {
+                Ok(()) => {
+                    self.used_arguments.push((arg, (*value).clone()));
+                    args.next();
+                }
+                // Leave the next argument alone, it might be an unrelated one
+                Err(message) => self.report(source, message),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, iter, ops::Deref, process};
+
+    use super::*;
+
+    /// An empty game directory unique to the test, deleted again once dropped.
+    struct GameDir(PathBuf);
+
+    impl Deref for GameDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for GameDir {
+        fn drop(&mut self) {
+            _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn game_dir(name: &str) -> GameDir {
+        let path = env::temp_dir().join(format!("doorstop_config_{name}_{}", process::id()));
+        _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        GameDir(path)
+    }
+
+    fn context(game_dir: &Path, args: &[&str], env: &[(&str, &str)]) -> LoadContext {
+        LoadContext {
+            game_dir: game_dir.to_path_buf(),
+            data_dir: None,
+            executable_name: "Game".to_string(),
+            working_dir: game_dir.to_path_buf(),
+            home_dir: None,
+            args: iter::once("Game").chain(args.iter().copied()).map(ToString::to_string).collect(),
+            env: env.iter().map(|(name, value)| ((*name).to_string(), (*value).to_string())).collect(),
+        }
+    }
+
+    /// Points `context` at a home directory inside of `dir` and returns where the per-user config file is looked up.
+    fn user_config_file(dir: &Path, context: &mut LoadContext) -> PathBuf {
+        let home_dir = dir.join("home");
+        context.home_dir = Some(home_dir.clone());
+
+        cfg_if! {
+            if #[cfg(windows)] {
+                let app_data = home_dir.join("AppData/Roaming");
+                context.env.push(("APPDATA".to_string(), app_data.to_string_lossy().into_owned()));
+                app_data.join("doorstop/Game.ini")
+            } else if #[cfg(target_os = "macos")] {
+                home_dir.join("Library/Application Support/doorstop/Game.ini")
+            } else {
+                home_dir.join(".config/doorstop/Game.ini")
+            }
+        }
+    }
+
+    fn messages(config: &Config) -> Vec<&str> {
+        config.diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect()
+    }
+
+    #[test]
+    fn defaults_without_config_file() {
+        let dir = game_dir("defaults");
+        let config = Config::load(&context(&dir, &[], &[])).unwrap();
+
+        assert_eq!(config.file_path, None);
+        assert!(config.enabled);
+        assert_eq!(config.entrypoint, "Doorstop.Entrypoint:Start");
+        assert!(config.sources.is_empty());
+        assert!(config.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn config_file_discovery_order() {
+        let dir = game_dir("discovery");
+        let find = |args: &[&str], env: &[(&str, &str)]| {
+            let mut context = context(&dir, args, env);
+            user_config_file(&dir, &mut context);
+            Config::find_config_file(&context)
+        };
+
+        assert_eq!(find(&[], &[]).unwrap(), None);
+
+        let user_file = user_config_file(&dir, &mut LoadContext::default());
+        fs::create_dir_all(user_file.parent().unwrap()).unwrap();
+        fs::write(&user_file, "").unwrap();
+        assert_eq!(find(&[], &[]).unwrap(), Some(user_file));
+
+        fs::write(dir.join(CONFIG_FILE_NAME), "").unwrap();
+        assert_eq!(find(&[], &[]).unwrap(), Some(dir.join(CONFIG_FILE_NAME)));
+
+        fs::write(dir.join("env.ini"), "").unwrap();
+        fs::write(dir.join("cli.ini"), "").unwrap();
+        assert_eq!(find(&[], &[("DOORSTOP_CONFIG", "env.ini")]).unwrap(), Some(dir.join("env.ini")));
+        assert_eq!(
+            find(&["--doorstop-config", "cli.ini"], &[("DOORSTOP_CONFIG", "env.ini")]).unwrap(),
+            Some(dir.join("cli.ini"))
+        );
+
+        // An explicit file has to exist, instead of falling back to the other ones
+        let error = find(&["--doorstop-config", "missing.ini"], &[]).unwrap_err();
+        assert_eq!(error.to_string(), format!("Config file {} doesn't exist", dir.join("missing.ini").display()));
+        assert!(Config::load(&context(&dir, &[], &[("DOORSTOP_CONFIG", "missing.ini")])).is_err());
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn xdg_config_home_overrides_home_directory() {
+        let dir = game_dir("xdg");
+        let xdg_file = dir.join("xdg/doorstop/Game.ini");
+        fs::create_dir_all(xdg_file.parent().unwrap()).unwrap();
+        fs::write(&xdg_file, "").unwrap();
+
+        let xdg_config_home = dir.join("xdg");
+        let mut context = context(&dir, &[], &[("XDG_CONFIG_HOME", xdg_config_home.to_str().unwrap())]);
+        let home_file = user_config_file(&dir, &mut context);
+        fs::create_dir_all(home_file.parent().unwrap()).unwrap();
+        fs::write(&home_file, "").unwrap();
+
+        assert_eq!(Config::find_config_file(&context).unwrap(), Some(xdg_file));
+
+        // An empty XDG_CONFIG_HOME counts as unset
+        context.env = vec![("XDG_CONFIG_HOME".to_string(), String::new())];
+        assert_eq!(Config::find_config_file(&context).unwrap(), Some(home_file));
+    }
+
+    #[test]
+    fn command_line_overrides_environment_overrides_file() {
+        let dir = game_dir("precedence");
+        let file_path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&file_path, "[General]\nentrypoint = File:Start\nentrypoint_args = file\nstrict = true\n").unwrap();
+
+        let context = context(
+            &dir,
+            &["--doorstop-entrypoint", "Cli:Start"],
+            &[("DOORSTOP_ENTRYPOINT", "Env:Start"), ("DOORSTOP_ENTRYPOINT_ARGS", "env")],
+        );
+        let config = Config::load(&context).unwrap();
+
+        assert_eq!(config.entrypoint, "Cli:Start");
+        assert_eq!(
+            config.sources[&("General", "entrypoint")],
+            Source::CommandLine("--doorstop-entrypoint".to_string())
+        );
+        assert_eq!(config.entrypoint_args, ["env"]);
+        assert_eq!(
+            config.sources[&("General", "entrypoint_args")],
+            Source::Environment("DOORSTOP_ENTRYPOINT_ARGS".to_string())
+        );
+        assert!(config.strict);
+        assert_eq!(
+            config.sources[&("General", "strict")],
+            Source::File {
+                path: file_path,
+                line: Some(4)
+            }
+        );
+        assert!(config.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn profile_overrides_general_section() {
+        let dir = game_dir("profile");
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "[General]\nentrypoint = General:Start\n\n[Profile:Other]\nmatch = Other*\nentrypoint_args = other\n\n[Profile:Game]\nmatch = ga*\nentrypoint = Game:Start\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&context(&dir, &[], &[])).unwrap();
+        assert_eq!(config.profile.as_deref(), Some("Game"));
+        assert_eq!(config.entrypoint, "Game:Start");
+        assert_eq!(config.entrypoint_args, Vec::<String>::new());
+
+        let config = Config::load(&context(&dir, &["--doorstop-profile", "Other"], &[])).unwrap();
+        assert_eq!(config.profile.as_deref(), Some("Other"));
+        assert_eq!(config.entrypoint, "General:Start");
+        assert_eq!(config.entrypoint_args, ["other"]);
+    }
+
+    #[test]
+    fn profile_from_included_file_overrides_general_section() {
+        let dir = game_dir("included_profile");
+        fs::write(dir.join("profiles.ini"), "[Profile:Game]\nmatch = Game\nentrypoint = Profile:Start\n").unwrap();
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "[General]\ninclude = profiles.ini\nentrypoint = Main:Start\nentrypoint_args = main\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&context(&dir, &[], &[])).unwrap();
+        assert!(config.diagnostics.is_empty(), "{:?}", messages(&config));
+        assert_eq!(config.profile.as_deref(), Some("Game"));
+        assert_eq!(config.entrypoint, "Profile:Start");
+        assert_eq!(config.entrypoint_args, ["main"]);
+
+        let config = Config::load(&context(&dir, &["--doorstop-entrypoint", "Cli:Start"], &[])).unwrap();
+        assert_eq!(config.entrypoint, "Cli:Start");
+    }
+
+    #[test]
+    fn including_file_and_drop_ins_override_includes() {
+        let dir = game_dir("include");
+        fs::write(
+            dir.join("base.ini"),
+            "[General]\nentrypoint = Base:Start\nentrypoint_args = base\nstrict = true\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "[General]\ninclude = base.ini\nentrypoint = Main:Start\nentrypoint_args = main\n\n[Profile:Game]\nmatch = Game\nentrypoint_ready_timeout = 500\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.join("doorstop_config.d")).unwrap();
+        fs::write(
+            dir.join("doorstop_config.d/10-args.ini"),
+            "[General]\nentrypoint_args = drop-in\nentrypoint_ready_timeout = 10\n",
+        )
+        .unwrap();
+        fs::write(dir.join("doorstop_config.d/20-profile.ini"), "[Profile:Game]\nstop_method = Stop\n").unwrap();
+
+        let config = Config::load(&context(&dir, &[], &[])).unwrap();
+
+        assert!(config.diagnostics.is_empty(), "{:?}", messages(&config));
+        assert!(config.strict);
+        assert_eq!(config.entrypoint, "Main:Start");
+        assert_eq!(config.entrypoint_args, ["drop-in"]);
+        assert_eq!(
+            config.fragment_paths,
+            [
+                dir.join("base.ini"),
+                dir.join("doorstop_config.d/10-args.ini"),
+                dir.join("doorstop_config.d/20-profile.ini")
+            ]
+        );
+
+        // The profile still overrides the drop-ins, which can add to it as well
+        assert_eq!(config.profile.as_deref(), Some("Game"));
+        assert_eq!(config.entrypoint_ready_timeout, 500);
+        assert_eq!(config.stop_method.as_deref(), Some("Stop"));
+    }
+
+    #[test]
+    fn ignore_disable_switch_keeps_file_value() {
+        let dir = game_dir("ignore_disable_switch");
+        fs::write(dir.join(CONFIG_FILE_NAME), "[General]\nenabled = true\nignore_disable_switch = true\n").unwrap();
+
+        let config = Config::load(&context(&dir, &["--doorstop-disable"], &[("DOORSTOP_ENABLED", "false")])).unwrap();
+        assert!(config.enabled);
+
+        // The marker file can't be overridden by anything
+        fs::write(dir.join(DISABLED_MARKER_FILE_NAME), "").unwrap();
+        let config = Config::load(&context(&dir, &[], &[])).unwrap();
+        assert!(!config.enabled);
+    }
+
+    #[test]
+    fn disable_switches() {
+        let dir = game_dir("disable_switches");
+        let disabled_by = |args: &[&str], env: &[(&str, &str)]| {
+            let config = Config::load(&context(&dir, args, env)).unwrap();
+            (!config.enabled).then(|| config.describe_source("General", "enabled"))
+        };
+
+        assert_eq!(disabled_by(&[], &[]), None);
+        assert_eq!(disabled_by(&["--doorstop-disable"], &[]).as_deref(), Some("argument --doorstop-disable"));
+        assert_eq!(
+            disabled_by(&[], &[("DOORSTOP_ENABLED", "false")]).as_deref(),
+            Some("environment variable DOORSTOP_ENABLED")
+        );
+
+        let marker_path = dir.join(DISABLED_MARKER_FILE_NAME);
+        fs::write(&marker_path, "").unwrap();
+        assert_eq!(disabled_by(&[], &[("DOORSTOP_ENABLED", "true")]), Some(marker_path.display().to_string()));
+    }
+
+    #[test]
+    fn ignore_disable_switch_from_environment() {
+        let dir = game_dir("ignore_disable_switch_env");
+        let config = Config::load(&context(
+            &dir,
+            &["--doorstop-disable", "--doorstop-enabled", "false"],
+            &[
+                ("DOORSTOP_ENABLED", "false"),
+                ("DOORSTOP_ENABLE", "false"),
+                ("DOORSTOP_IGNORE_DISABLED_ENV", "true"),
+            ],
+        ))
+        .unwrap();
+
+        assert!(config.enabled);
+        assert_eq!(config.describe_source("General", "enabled"), "default");
+        assert!(config.diagnostics.is_empty(), "{:?}", messages(&config));
+    }
+
+    #[test]
+    fn current_environment_variable_overrides_legacy_one() {
+        let dir = game_dir("legacy");
+        let config = Config::load(&context(
+            &dir,
+            &[],
+            &[("DOORSTOP_ENABLE", "false"), ("DOORSTOP_ENABLED", "true"), ("DOORSTOP_STRICT", "true")],
+        ))
+        .unwrap();
+
+        assert!(config.enabled);
+        assert!(config.diagnostics.is_empty());
+        assert_eq!(
+            config.deprecations.iter().map(|deprecation| deprecation.message.as_str()).collect::<Vec<_>>(),
+            ["deprecated environment variable, use DOORSTOP_ENABLED instead"]
+        );
+        assert!(config.report_diagnostics().is_ok());
+    }
+
+    #[test]
+    fn to_ini_round_trips() {
+        let dir = game_dir("to_ini");
+        fs::write(
+            dir.join("source.ini"),
+            "[General]\ntarget_assembly = a.dll;sub/b.dll\nentrypoint = Price$$:Start\n\n[Target:Plugin]\npath = plugin.dll\non_failure = skip\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&context(
+            &dir,
+            &["--doorstop-config", "source.ini", "--doorstop-mono-debug-enabled", "true"],
+            &[],
+        ))
+        .unwrap();
+        assert!(config.diagnostics.is_empty());
+
+        fs::write(dir.join(CONFIG_FILE_NAME), config.to_ini()).unwrap();
+        let written = Config::load(&context(&dir, &[], &[])).unwrap();
+
+        assert!(written.diagnostics.is_empty(), "{:?}", written.diagnostics);
+        assert_eq!(written.target_assembly, [dir.join("a.dll"), dir.join("sub/b.dll")]);
+        assert_eq!(written.entrypoint, "Price$:Start");
+        assert!(written.mono_debug_enabled);
+        assert_eq!(written.targets.len(), 1);
+        assert_eq!(written.targets[0].path, dir.join("plugin.dll"));
+        assert_eq!(written.targets[0].on_failure, OnFailure::Skip);
+        assert_eq!(written.sources.len(), config.sources.len());
+    }
+
+    #[test]
+    fn environment_section_applies_entries_in_order() {
+        let dir = game_dir("environment");
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "[Environment]\nMONO_ENV_OPTIONS = --debug\nLIB_PATH += ${GAME_DIR}/lib\nLIB_PATH ^= first\nUNITY_LOG_FILE -=\nDERIVED = ${LIB_PATH} $${LITERAL}\n\n\
+             [Profile:Game]\nmatch = Game\nEnvironment.MONO_ENV_OPTIONS = --profile\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&context(&dir, &[], &[("LIB_PATH", "existing"), ("UNITY_LOG_FILE", "log.txt")])).unwrap();
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+
+        let lib_path = format!("first{PATH_LIST_SEPARATOR}existing{PATH_LIST_SEPARATOR}{}/lib", dir.display());
+        let environment: Vec<(&str, Option<&str>)> = config
+            .environment
+            .iter()
+            .map(|environment| (environment.name.as_str(), environment.value.as_deref()))
+            .collect();
+
+        assert_eq!(
+            environment,
+            [
+                ("MONO_ENV_OPTIONS", Some("--profile")),
+                ("LIB_PATH", Some(lib_path.as_str())),
+                ("UNITY_LOG_FILE", None),
+                ("DERIVED", Some(format!("{lib_path} ${{LITERAL}}").as_str())),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_names_and_invalid_values_are_reported() {
+        let dir = game_dir("diagnostics");
+        let file_path = dir.join(CONFIG_FILE_NAME);
+        fs::write(
+            &file_path,
+            "[General]\nenabled = maybe\nentrypoint_arg = a\n\n[UnityMono]\ndebug_enable = true\n\n[Il2cpp]\ncoreclr_path = coreclr.dll\n\n[Unrelated]\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&context(&dir, &["--doorstop-entrypont", "Cli:Start"], &[("DOORSTOP_ENTRYPONT", "Env:Start")])).unwrap();
+
+        assert_eq!(
+            messages(&config),
+            [
+                "invalid value `maybe`, expected `true` or `false`",
+                "unknown key `entrypoint_arg` in section `[General]`, did you mean `entrypoint_args`?",
+                "unknown key `debug_enable` in section `[UnityMono]`, did you mean `debug_enabled`?",
+                "unknown section `[Il2cpp]`, did you mean `Il2Cpp`?",
+                "unknown section `[Unrelated]`",
+                "unknown environment variable, did you mean `DOORSTOP_ENTRYPOINT`?",
+                "unknown argument, did you mean `--doorstop-entrypoint`?",
+            ]
+        );
+        assert_eq!(
+            config.diagnostics[0].source,
+            Source::File {
+                path: file_path.clone(),
+                line: Some(2)
+            }
+        );
+        assert_eq!(
+            config.diagnostics[3].source,
+            Source::File {
+                path: file_path,
+                line: Some(8)
+            }
+        );
+        assert!(config.enabled);
+        assert!(config.report_diagnostics().is_ok());
+
+        let config = Config::load(&context(&dir, &["--doorstop-strict", "true"], &[])).unwrap();
+        let error = config.report_diagnostics().unwrap_err().to_string();
+        assert!(error.starts_with("Invalid config (strict mode is enabled):\n"), "{error}");
+        assert!(error.contains("did you mean `entrypoint_args`?"), "{error}");
+    }
+
+    #[test]
+    fn unparsable_config_file_is_reported() {
+        let dir = game_dir("unparsable");
+        fs::write(dir.join(CONFIG_FILE_NAME), "[General]\nenabled = true\n[UnityMono\n").unwrap();
+        let config = Config::load(&context(&dir, &[], &[])).unwrap();
+
+        assert_eq!(config.diagnostics.len(), 1);
+        assert!(messages(&config)[0].starts_with("failed to parse config file"), "{:?}", messages(&config));
+    }
+
+    #[test]
+    fn empty_bool_is_ignored() {
+        let dir = game_dir("empty_bool");
+        fs::write(dir.join(CONFIG_FILE_NAME), "[General]\nenabled =\nstrict = true\n").unwrap();
+        let config = Config::load(&context(&dir, &[], &[("DOORSTOP_ENABLED", "")])).unwrap();
+
+        assert!(config.enabled);
+        assert!(config.diagnostics.is_empty(), "{:?}", messages(&config));
+    }
+
+    #[test]
+    fn to_environment_round_trips() {
+        let dir = game_dir("to_environment");
+        let config = Config::load(&context(&dir, &["--doorstop-target-assembly", "a.dll", "--doorstop-strict", "true"], &[])).unwrap();
+
+        let env = config.to_environment();
+        let env: Vec<(&str, &str)> = env.iter().map(|(name, value)| (*name, value.as_str())).collect();
+        let loaded = Config::load(&context(&dir, &[], &env)).unwrap();
+
+        assert!(loaded.strict);
+        assert_eq!(loaded.target_assembly, [dir.join("a.dll")]);
+        assert_eq!(loaded.all_targets().count(), 1);
+    }
+
+    #[test]
+    fn find_doorstop_arguments_includes_values() {
+        let dir = game_dir("find_arguments");
+        let args = [
+            "--doorstop-print-config",
+            "-batchmode",
+            "--doorstop-enabled",
+            "false",
+            "--doorstop-unknown",
+            "x",
+        ];
+        let config = Config::load(&context(&dir, &args, &[])).unwrap();
+
+        let args = iter::once("Game").chain(args).map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(config.find_doorstop_arguments(&args), [false, true, false, true, true, false, false]);
+    }
+
+    #[test]
+    fn find_doorstop_arguments_keeps_rejected_values() {
+        let dir = game_dir("find_rejected_arguments");
+        let args = ["--doorstop-enabled", "-batchmode", "--doorstop-target-assembly", "a.dll"];
+        let config = Config::load(&context(&dir, &args, &[])).unwrap();
+
+        assert_eq!(messages(&config), ["invalid value `-batchmode`, expected `true` or `false`"]);
+
+        let args = iter::once("Game").chain(args).map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(config.find_doorstop_arguments(&args), [false, true, false, true, true]);
+    }
+
+    #[test]
+    fn placeholder_expansion() {
+        let context = LoadContext {
+            game_dir: PathBuf::from("/game"),
+            executable_name: "Game".to_string(),
+            home_dir: Some(PathBuf::from("/home/user")),
+            env: vec![("NAME".to_string(), "value".to_string())],
+            ..LoadContext::default()
+        };
+
+        let cases: &[(&str, Result<&str, &str>)] = &[
+            ("", Ok("")),
+            ("${GAME_DIR}/BepInEx", Ok("/game/BepInEx")),
+            ("${EXE_NAME}.log", Ok("Game.log")),
+            ("${ENV:NAME}-${ENV:NAME}", Ok("value-value")),
+            ("${ENV:MISSING}", Err("environment variable `MISSING` is not set")),
+            ("${DATA_DIR}", Err("couldn't find the game's data directory")),
+            ("${GAME_DIRR}", Err("unknown placeholder `${GAME_DIRR}`, did you mean `GAME_DIR`?")),
+            ("${NAME}", Err("unknown placeholder `${NAME}`")),
+            ("${GAME_DIR", Err("unterminated placeholder in `${GAME_DIR`")),
+            ("$${GAME_DIR} costs $5", Ok("${GAME_DIR} costs $5")),
+            ("~", Ok("/home/user")),
+            ("~/mods", Ok("/home/user/mods")),
+            ("~\\mods", Ok("/home/user\\mods")),
+            ("~user/mods", Ok("~user/mods")),
+            ("mods/~", Ok("mods/~")),
+        ];
+
+        for (text, expected) in cases {
+            let expected = expected.map(ToString::to_string).map_err(ToString::to_string);
+            assert_eq!(expand_placeholders(text, &context), expected, "{text}");
+        }
+
+        // The [Environment] section resolves any other name as a variable
+        let variables = |name: &str| (name == "OTHER").then(|| "other".to_string());
+        assert_eq!(
+            expand_placeholders_with("${OTHER} ${GAME_DIR}", &context, Some(&variables)).as_deref(),
+            Ok("other /game")
+        );
+        assert_eq!(
+            expand_placeholders_with("${ENV:NAME}", &context, Some(&variables)),
+            Err("environment variable `NAME` is not set".to_string())
+        );
+
+        let context = LoadContext { home_dir: None, ..context };
+        assert_eq!(expand_placeholders("~/mods", &context), Err("couldn't find the home directory".to_string()));
+    }
+
+    #[test]
+    fn glob_matching() {
+        let cases = [
+            ("*Server*", "GameServer", true),
+            ("*server*", "GameServer", true),
+            ("Game", "Game", true),
+            ("Game", "GameServer", false),
+            ("Server", "GameServer", false),
+            ("Game*", "Game", true),
+            ("*Server", "GameServer", true),
+            ("*Game", "GameServer", false),
+            ("Game?erver", "GameServer", true),
+            ("Game?", "Game", false),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*c", "abcd", false),
+            ("*", "", true),
+            ("?", "", false),
+            ("", "", true),
+            ("", "Game", false),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_matches(pattern, text), expected, "`{pattern}` against `{text}`");
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::{borrow::Cow, fs, mem, path::Path};
+
+use crate::{LEGACY_KEYS, LegacyKey, find_legacy_key};
+
+/// Rewrites the legacy keys of a config file to their current names, the original file is kept with a `.bak` extension.
+/// Only the legacy sections are touched, comments, blank lines and the order of everything else stay as they are.
+/// Returns `false` if there was nothing to migrate.
+pub fn migrate_config_file(path: &Path) -> anyhow::Result<bool> {
+    let text = fs::read_to_string(path)?;
+    let Some(migrated) = migrate_text(&text) else {
+        return Ok(false);
+    };
+
+    let mut backup_path = path.as_os_str().to_owned();
+    backup_path.push(".bak");
+    fs::copy(path, &backup_path)?;
+
+    fs::write(path, migrated)?;
+
+    Ok(true)
+}
+
+/// A section as written in the file, from its header up to the next one.
+struct Section<'a> {
+    name: Option<String>,
+    /// `None` for the lines before the first header, or a legacy section merged into an existing one.
+    header: Option<Cow<'a, str>>,
+    lines: Vec<Cow<'a, str>>,
+}
+
+fn parse_header(line: &str) -> Option<&str> {
+    line.trim().strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+/// Returns the key of a `key = value` line, `None` for blank lines and comments.
+fn parse_key(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with([';', '#']) {
+        return None;
+    }
+
+    line.find(['=', ':']).map(|separator| line[..separator].trim())
+}
+
+/// Replaces the key of a `key = value` line, keeping the indentation and the value as written.
+fn rename_key(line: &str, new_key: &str) -> String {
+    let (key, rest) = line.split_at(line.find(['=', ':']).unwrap());
+    let indent = &key[..key.len() - key.trim_start().len()];
+    let padding = &key[key.trim_end().len()..];
+    format!("{indent}{new_key}{padding}{rest}")
+}
+
+/// Comments and blank lines stay in a renamed section, a merged one moves its comments along with its keys.
+fn flush<'a>(
+    renamed: bool,
+    new_name: Option<&'static str>,
+    lines: &mut Vec<Cow<'a, str>>,
+    block: &mut Vec<Cow<'a, str>>,
+    moved: &mut Vec<(&'static str, Vec<Cow<'a, str>>)>,
+) {
+    if renamed {
+        lines.append(block);
+        return;
+    }
+
+    block.retain(|line| !line.trim().is_empty());
+    if let Some(new_name) = new_name
+        && !block.is_empty()
+    {
+        moved.push((new_name, mem::take(block)));
+    }
+
+    block.clear();
+}
+
+fn is_legacy_section(name: &str) -> bool {
+    LEGACY_KEYS.iter().any(|legacy_key| legacy_key.section == name)
+}
+
+/// Migrates the legacy sections of a config file, `None` if there aren't any.
+///
+/// A legacy section is renamed to the section its keys moved to, or merged into it if the file already has one.
+/// Keys moving to another section take the comments right above them along.
+/// Keys already set in their new section, or that aren't supported anymore, are dropped.
+fn migrate_text(text: &str) -> Option<String> {
+    let mut sections = vec![Section {
+        name: None,
+        header: None,
+        lines: Vec::new(),
+    }];
+
+    for line in text.lines() {
+        if let Some(name) = parse_header(line) {
+            sections.push(Section {
+                name: Some(name.to_string()),
+                header: Some(Cow::Borrowed(line)),
+                lines: Vec::new(),
+            });
+        } else {
+            sections.last_mut().unwrap().lines.push(Cow::Borrowed(line));
+        }
+    }
+
+    if !sections.iter().any(|section| section.name.as_deref().is_some_and(is_legacy_section)) {
+        return None;
+    }
+
+    let mut existing_sections: Vec<String> = Vec::new();
+    let mut existing_keys: Vec<(String, String)> = Vec::new();
+    for section in &sections {
+        if let Some(name) = section.name.as_ref().filter(|name| !is_legacy_section(name)) {
+            existing_sections.push(name.clone());
+            existing_keys.extend(
+                section
+                    .lines
+                    .iter()
+                    .filter_map(|line| parse_key(line))
+                    .map(|key| (name.clone(), key.to_string())),
+            );
+        }
+    }
+
+    let mut moved: Vec<(&'static str, Vec<Cow<str>>)> = Vec::new();
+
+    for section in &mut sections {
+        let Some(name) = section.name.clone().filter(|name| is_legacy_section(name)) else {
+            continue;
+        };
+
+        let new_name = LEGACY_KEYS
+            .iter()
+            .find_map(|legacy_key| legacy_key.replacement.filter(|_| legacy_key.section == name))
+            .map(|(new_section, _)| new_section);
+
+        let renamed = new_name.filter(|new_name| !existing_sections.iter().any(|existing| existing == new_name));
+        if let Some(new_name) = renamed {
+            section.header = section.header.take().map(|header| Cow::Owned(header.replacen(name.as_str(), new_name, 1)));
+            section.name = Some(new_name.to_string());
+            existing_sections.push(new_name.to_string());
+        } else {
+            section.header = None;
+            section.name = None;
+        }
+
+        let mut block = Vec::new();
+        for line in mem::take(&mut section.lines) {
+            let Some(key) = parse_key(&line) else {
+                let is_blank = line.trim().is_empty();
+                block.push(line);
+                if is_blank {
+                    flush(renamed.is_some(), new_name, &mut section.lines, &mut block, &mut moved);
+                }
+                continue;
+            };
+
+            match find_legacy_key(&name, key) {
+                Some(LegacyKey {
+                    replacement: Some((new_section, new_key)),
+                    ..
+                }) => {
+                    if existing_keys.iter().any(|(section, key)| section == new_section && key == new_key) {
+                        block.clear();
+                        continue;
+                    }
+
+                    existing_keys.push(((*new_section).to_string(), (*new_key).to_string()));
+                    block.push(Cow::Owned(rename_key(&line, new_key)));
+
+                    if renamed == Some(*new_section) {
+                        section.lines.append(&mut block);
+                    } else {
+                        moved.push((new_section, mem::take(&mut block)));
+                    }
+                }
+                Some(LegacyKey { replacement: None, .. }) => block.clear(),
+                // Unknown keys are reported when loading, keep them for the user to fix
+                None => {
+                    block.push(line);
+                    flush(renamed.is_some(), new_name, &mut section.lines, &mut block, &mut moved);
+                }
+            }
+        }
+
+        flush(renamed.is_some(), new_name, &mut section.lines, &mut block, &mut moved);
+    }
+
+    for (new_section, lines) in moved {
+        let index = if let Some(index) = sections.iter().position(|section| section.name.as_deref() == Some(new_section)) {
+            index
+        } else {
+            if let Some(last) = sections.last_mut().and_then(|section| section.lines.last())
+                && !last.trim().is_empty()
+            {
+                sections.last_mut().unwrap().lines.push(Cow::Borrowed(""));
+            }
+
+            sections.push(Section {
+                name: Some(new_section.to_string()),
+                header: Some(Cow::Owned(format!("[{new_section}]"))),
+                lines: Vec::new(),
+            });
+            sections.len() - 1
+        };
+
+        let section_lines = &mut sections[index].lines;
+        let position = section_lines.iter().rposition(|line| !line.trim().is_empty()).map_or(0, |i| i + 1);
+        section_lines.splice(position..position, lines);
+    }
+
+    let line_ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut output: Vec<Cow<str>> = Vec::new();
+    for section in sections {
+        output.extend(section.header);
+        output.extend(section.lines);
+    }
+
+    let mut output = output.join(line_ending);
+    if text.ends_with('\n') {
+        output.push_str(line_ending);
+    }
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_legacy_sections_in_place() {
+        assert!(migrate_text("[General]\nenabled = true\n").is_none());
+
+        let text = "\
+; Game config
+
+[General]
+; Keep this one
+enabled = false
+
+[UnityDoorstop]
+# Enables doorstop
+enabled=true
+# Assembly to run
+targetAssembly = BepInEx\\core\\BepInEx.Preloader.dll
+# Search path
+dllSearchPathOverride = libs
+
+[MonoBackend]
+corlibDir =
+debugEnabled = true
+";
+
+        let expected = "\
+; Game config
+
+[General]
+; Keep this one
+enabled = false
+# Assembly to run
+target_assembly = BepInEx\\core\\BepInEx.Preloader.dll
+
+[UnityMono]
+debug_enabled = true
+# Search path
+dll_search_path_override = libs
+";
+
+        assert_eq!(migrate_text(text).unwrap(), expected);
+
+        let text = "[UnityDoorstop]\r\n  enabled = true\r\n  dllSearchPathOverride = libs\r\n";
+        assert_eq!(
+            migrate_text(text).unwrap(),
+            "[General]\r\n  enabled = true\r\n\r\n[UnityMono]\r\n  dll_search_path_override = libs\r\n"
+        );
+    }
+}
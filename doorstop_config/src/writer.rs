@@ -0,0 +1,70 @@
+use std::fmt::Write;
+
+use crate::{Config, ENVIRONMENT_SECTION, OnFailure, SETTINGS, TARGET_SECTION_PREFIX, escape};
+
+impl Config {
+    /// Formats the config as a config file with every setting described by a comment.
+    /// Settings left at their default are commented out, so the file only overrides what was actually set.
+    /// Profiles aren't written, only the values they resulted in, same for the final values of `[Environment]` variables.
+    #[must_use]
+    pub fn to_ini(&self) -> String {
+        let mut output = String::new();
+        let mut section = None;
+
+        for setting in SETTINGS {
+            if section != Some(setting.section) {
+                writeln!(output, "[{}]\n", setting.section).unwrap();
+                section = Some(setting.section);
+            }
+
+            let value = (setting.text)(self).unwrap_or_default();
+            let comment = if self.sources.contains_key(&(setting.section, setting.key)) {
+                ""
+            } else {
+                "; "
+            };
+
+            writeln!(output, "; {}", setting.description).unwrap();
+            writeln!(output, "{}\n", format!("{comment}{} = {value}", setting.key).trim_end()).unwrap();
+        }
+
+        if !self.environment.is_empty() {
+            writeln!(output, "[{ENVIRONMENT_SECTION}]").unwrap();
+
+            for environment in &self.environment {
+                match environment.value.as_ref() {
+                    Some(value) => writeln!(output, "{} = {}", environment.name, escape(value)).unwrap(),
+                    None => writeln!(output, "{} -=", environment.name).unwrap(),
+                }
+            }
+
+            output.push('\n');
+        }
+
+        for target in &self.targets {
+            writeln!(output, "[{TARGET_SECTION_PREFIX}{}]", target.name).unwrap();
+            writeln!(output, "path = {}", escape(&target.path.to_string_lossy())).unwrap();
+
+            if let Some(entrypoint) = target.entrypoint.as_ref() {
+                writeln!(output, "entrypoint = {}", escape(entrypoint)).unwrap();
+            }
+
+            if target.on_failure == OnFailure::Skip {
+                writeln!(output, "on_failure = skip").unwrap();
+            }
+        }
+
+        output
+    }
+
+    /// Returns the environment variables that reproduce every setting that isn't left at its default.
+    /// `[Target:<name>]` sections have no environment variables, so they are left out.
+    #[must_use]
+    pub fn to_environment(&self) -> Vec<(&'static str, String)> {
+        SETTINGS
+            .iter()
+            .filter(|setting| self.sources.contains_key(&(setting.section, setting.key)))
+            .filter_map(|setting| Some((setting.env?, (setting.text)(self).unwrap_or_default())))
+            .collect()
+    }
+}
@@ -0,0 +1,188 @@
+//! Import table hooking, the same one doorstop uses for its own patches.
+//! Modules are opaque pointers to a [`plthook::ObjectFile`], freed with [`doorstop_hook_close`].
+
+use std::ffi::{CStr, c_char, c_void};
+
+use log::debug;
+use plthook::{ErrorKind, ObjectFile};
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookResult {
+    Ok = 0,
+    /// The module doesn't import the symbol.
+    NotFound = 1,
+    InvalidArgument = 2,
+    Error = -1,
+}
+
+/// Replaces `symbol_name` in `object`'s import table with `address`, returns the address the symbol resolved to before.
+///
+/// # Safety
+/// `address` has to be a function with the same signature as the replaced symbol.
+pub unsafe fn replace_symbol(object: &ObjectFile, symbol_name: &str, address: *const c_void) -> plthook::Result<*const c_void> {
+    let mut entry = unsafe { object.replace(symbol_name, address)? };
+
+    let original_address = {
+        #[cfg(windows)]
+        {
+            entry.original_address()
+        }
+
+        #[cfg(unix)]
+        {
+            if std::env::var("LD_BIND_NOW").is_ok_and(|val| val == "1") {
+                entry.original_address()
+            } else {
+                // PLT's lazy binding would overwrite our hook on the first call, so resolve the symbol ourselves
+                let symbol_name = std::ffi::CString::new(symbol_name).unwrap();
+                unsafe { libc::dlsym(libc::RTLD_NEXT, symbol_name.as_ptr()) }
+            }
+        }
+    };
+
+    entry.discard();
+
+    Ok(original_address)
+}
+
+fn open(result: plthook::Result<ObjectFile>) -> *mut ObjectFile {
+    match result {
+        Ok(object) => Box::into_raw(Box::new(object)),
+        Err(e) => {
+            debug!("Failed to open module: {e}");
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Opens an already loaded module by its file name (e.g. `UnityPlayer.dll`), or the main program if `name` is `null`.
+/// Returns `null` if it isn't loaded.
+///
+/// # Safety
+/// `name` has to be `null` or a nul-terminated UTF-8 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_hook_open(name: *const c_char) -> *mut ObjectFile {
+    if name.is_null() {
+        return open(ObjectFile::open_main_program());
+    }
+
+    let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+        return std::ptr::null_mut();
+    };
+
+    #[cfg(windows)]
+    let handle = {
+        use windows::{Win32::System::LibraryLoader::GetModuleHandleW, core::HSTRING};
+
+        unsafe { GetModuleHandleW(&HSTRING::from(name)) }.map_or(std::ptr::null_mut(), |module| module.0)
+    };
+
+    // The module stays loaded for as long as the process runs
+    #[cfg(unix)]
+    let handle = {
+        let name = std::ffi::CString::new(name).unwrap();
+        unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_NOLOAD) }
+    };
+
+    if handle.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    unsafe { doorstop_hook_open_handle(handle) }
+}
+
+/// Opens a module by its `HMODULE` or `dlopen` handle, returns `null` on failure.
+///
+/// # Safety
+/// `handle` has to be a valid handle of a loaded module.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_hook_open_handle(handle: *const c_void) -> *mut ObjectFile {
+    if handle.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    open(unsafe { ObjectFile::open_by_handle(handle) })
+}
+
+/// Frees a module returned by [`doorstop_hook_open`] or [`doorstop_hook_open_handle`], replaced symbols stay replaced.
+///
+/// # Safety
+/// `module` has to be `null` or a module that wasn't closed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_hook_close(module: *mut ObjectFile) {
+    if !module.is_null() {
+        drop(unsafe { Box::from_raw(module) });
+    }
+}
+
+/// Replaces the imported `symbol` with `replacement`, the previous address is written to `original` unless it's `null`.
+///
+/// # Safety
+/// `module` has to be an open module, `symbol` a nul-terminated string and `replacement` a function with the same signature as the symbol.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_hook_replace(
+    module: *const ObjectFile,
+    symbol: *const c_char,
+    replacement: *const c_void,
+    original: *mut *const c_void,
+) -> HookResult {
+    let Some((object, symbol)) = (unsafe { arguments(module, symbol) }) else {
+        return HookResult::InvalidArgument;
+    };
+
+    if replacement.is_null() {
+        return HookResult::InvalidArgument;
+    }
+
+    match unsafe { replace_symbol(object, symbol, replacement) } {
+        Ok(original_address) => {
+            debug!(symbol = symbol; "Hooking {symbol} for a plugin");
+            if !original.is_null() {
+                unsafe { *original = original_address };
+            }
+            HookResult::Ok
+        }
+        Err(e) => result_from_error(&e),
+    }
+}
+
+/// Puts `original` (as returned by [`doorstop_hook_replace`]) back into the import table.
+///
+/// # Safety
+/// `module` has to be an open module, `symbol` a nul-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_hook_restore(module: *const ObjectFile, symbol: *const c_char, original: *const c_void) -> HookResult {
+    let Some((object, symbol)) = (unsafe { arguments(module, symbol) }) else {
+        return HookResult::InvalidArgument;
+    };
+
+    if original.is_null() {
+        return HookResult::InvalidArgument;
+    }
+
+    match unsafe { object.replace(symbol, original) } {
+        Ok(entry) => {
+            entry.discard();
+            HookResult::Ok
+        }
+        Err(e) => result_from_error(&e),
+    }
+}
+
+unsafe fn arguments<'a>(module: *const ObjectFile, symbol: *const c_char) -> Option<(&'a ObjectFile, &'a str)> {
+    if module.is_null() || symbol.is_null() {
+        return None;
+    }
+
+    Some((unsafe { &*module }, unsafe { CStr::from_ptr(symbol) }.to_str().ok()?))
+}
+
+fn result_from_error(e: &plthook::Error) -> HookResult {
+    if matches!(e.kind(), ErrorKind::FunctionNotFound) {
+        HookResult::NotFound
+    } else {
+        debug!("Failed to replace symbol: {e}");
+        HookResult::Error
+    }
+}
@@ -0,0 +1,279 @@
+//! C ABI for managed code (and anything else loaded into the game) to query doorstop, write to its log and hook imports.
+//! Strings are UTF-8 and stay valid for the lifetime of the process.
+
+pub mod hook;
+
+use std::{
+    ffi::{CStr, CString, c_char, c_void},
+    path::Path,
+    ptr,
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+
+use log::Level;
+use plthook::ObjectFile;
+
+use crate::{find_data_folder, get_game_dir};
+
+/// Bumped whenever functions or [`DoorstopInfo`] fields are added, existing ones never change.
+/// `2` added the [`hook`] functions, `3` added [`doorstop_get_config`] and [`DoorstopApi`], `4` added [`doorstop_signal_ready`].
+pub const DOORSTOP_API_VERSION: u32 = 4;
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeKind {
+    /// No runtime was bootstrapped (yet).
+    Unknown = 0,
+    Mono = 1,
+    /// `CoreCLR` started by doorstop inside of an IL2CPP game.
+    CoreClr = 2,
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectionMethod {
+    Unknown = 0,
+    /// Loaded as `winhttp.dll` or `version.dll` from the game directory.
+    Proxy = 1,
+    /// Injected into the process by `doorstop_launcher`.
+    Injected = 2,
+    /// Loaded through `LD_PRELOAD` or `DYLD_INSERT_LIBRARIES`.
+    Preload = 3,
+    /// Linked into `doorstop_player`, which runs the game's `UnityMain` itself.
+    Player = 4,
+}
+
+#[repr(C)]
+pub struct DoorstopInfo {
+    /// Set by the caller to `size_of::<DoorstopInfo>()` of the version it was built against, only that many bytes are filled in.
+    pub size: usize,
+    pub api_version: u32,
+    pub runtime: RuntimeKind,
+    pub injection_method: InjectionMethod,
+    pub version: *const c_char,
+    pub game_dir: *const c_char,
+    /// `null` if the data folder couldn't be found.
+    pub data_dir: *const c_char,
+    /// `null` if no config file was used.
+    pub config_path: *const c_char,
+    /// Managed assemblies of the runtime, `null` until a runtime was bootstrapped.
+    pub managed_dir: *const c_char,
+}
+
+/// Passed to `doorstop_plugin_init` of native plugins, so they don't have to look up doorstop's exports themselves.
+#[repr(C)]
+pub struct DoorstopApi {
+    /// `size_of::<DoorstopApi>()`, newer versions only append fields.
+    pub size: usize,
+    pub api_version: u32,
+    pub get_info: unsafe extern "C" fn(info: *mut DoorstopInfo) -> bool,
+    pub get_config: unsafe extern "C" fn(section: *const c_char, key: *const c_char) -> *const c_char,
+    pub log: unsafe extern "C" fn(level: u32, message: *const c_char),
+    pub hook_open: unsafe extern "C" fn(name: *const c_char) -> *mut ObjectFile,
+    pub hook_open_handle: unsafe extern "C" fn(handle: *const c_void) -> *mut ObjectFile,
+    pub hook_close: unsafe extern "C" fn(module: *mut ObjectFile),
+    pub hook_replace:
+        unsafe extern "C" fn(module: *const ObjectFile, symbol: *const c_char, replacement: *const c_void, original: *mut *const c_void) -> hook::HookResult,
+    pub hook_restore: unsafe extern "C" fn(module: *const ObjectFile, symbol: *const c_char, original: *const c_void) -> hook::HookResult,
+    pub signal_ready: extern "C" fn(),
+}
+
+pub static DOORSTOP_API: DoorstopApi = DoorstopApi {
+    size: size_of::<DoorstopApi>(),
+    api_version: DOORSTOP_API_VERSION,
+    get_info: doorstop_get_info,
+    get_config: doorstop_get_config,
+    log: doorstop_log,
+    hook_open: hook::doorstop_hook_open,
+    hook_open_handle: hook::doorstop_hook_open_handle,
+    hook_close: hook::doorstop_hook_close,
+    hook_replace: hook::doorstop_hook_replace,
+    hook_restore: hook::doorstop_hook_restore,
+    signal_ready: doorstop_signal_ready,
+};
+
+struct Paths {
+    version: CString,
+    game_dir: Option<CString>,
+    data_dir: Option<CString>,
+    config_path: Option<CString>,
+}
+
+static RUNTIME: OnceLock<(RuntimeKind, CString)> = OnceLock::new();
+static INJECTION_METHOD: AtomicU32 = AtomicU32::new(InjectionMethod::Unknown as u32);
+
+fn to_cstring(path: &Path) -> Option<CString> {
+    CString::new(path.to_string_lossy().into_owned()).ok()
+}
+
+fn as_ptr(text: Option<&CString>) -> *const c_char {
+    text.map_or(ptr::null(), |text| text.as_ptr())
+}
+
+/// Called by the runtimes once they're bootstrapped, only the first call counts.
+pub(crate) fn set_runtime(kind: RuntimeKind, managed_dir: &Path) {
+    if let Some(managed_dir) = to_cstring(managed_dir) {
+        _ = RUNTIME.set((kind, managed_dir));
+    }
+}
+
+/// Called by the entry point that loaded doorstop if it isn't the default for the platform.
+pub fn set_injection_method(method: InjectionMethod) {
+    INJECTION_METHOD.store(method as u32, Ordering::Relaxed);
+}
+
+fn injection_method() -> InjectionMethod {
+    match INJECTION_METHOD.load(Ordering::Relaxed) {
+        1 => InjectionMethod::Proxy,
+        2 => InjectionMethod::Injected,
+        3 => InjectionMethod::Preload,
+        4 => InjectionMethod::Player,
+        _ if std::env::var_os("DOORSTOP_PLAYER").is_some() => InjectionMethod::Player,
+        _ if cfg!(windows) => InjectionMethod::Injected,
+        _ => InjectionMethod::Preload,
+    }
+}
+
+/// Returns [`DOORSTOP_API_VERSION`].
+#[unsafe(no_mangle)]
+pub extern "C" fn doorstop_api_version() -> u32 {
+    DOORSTOP_API_VERSION
+}
+
+/// Fills in `info` up to `info->size` bytes, returns `false` if `info` is `null` or too small to hold `size` and `api_version`.
+///
+/// # Safety
+/// `info` has to point to writable memory of at least `info->size` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_get_info(info: *mut DoorstopInfo) -> bool {
+    static PATHS: OnceLock<Paths> = OnceLock::new();
+
+    if info.is_null() {
+        return false;
+    }
+
+    let size = unsafe { (*info).size };
+    if size < size_of::<usize>() + size_of::<u32>() {
+        return false;
+    }
+
+    let paths = PATHS.get_or_init(|| Paths {
+        version: CString::new(env!("CARGO_PKG_VERSION")).unwrap(),
+        game_dir: get_game_dir().ok().as_deref().and_then(to_cstring),
+        data_dir: find_data_folder().as_deref().and_then(to_cstring),
+        config_path: crate::CONFIG.get().and_then(|config| config.file_path.as_deref()).and_then(to_cstring),
+    });
+
+    let runtime = RUNTIME.get();
+    let full = DoorstopInfo {
+        size: size.min(size_of::<DoorstopInfo>()),
+        api_version: DOORSTOP_API_VERSION,
+        runtime: runtime.map_or(RuntimeKind::Unknown, |(kind, _)| *kind),
+        injection_method: injection_method(),
+        version: paths.version.as_ptr(),
+        game_dir: as_ptr(paths.game_dir.as_ref()),
+        data_dir: as_ptr(paths.data_dir.as_ref()),
+        config_path: as_ptr(paths.config_path.as_ref()),
+        managed_dir: as_ptr(runtime.map(|(_, managed_dir)| managed_dir)),
+    };
+
+    // Older callers only know about a prefix of the struct
+    unsafe { ptr::copy_nonoverlapping((&raw const full).cast::<u8>(), info.cast::<u8>(), full.size) };
+
+    true
+}
+
+/// Returns a setting's value as it would be written in the config file (e.g. `true` or `a.dll;b.dll`), `null` if it's unset or unknown.
+///
+/// # Safety
+/// `section` and `key` have to be `null` or nul-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_get_config(section: *const c_char, key: *const c_char) -> *const c_char {
+    // The config never changes once loaded, so values are kept around for callers to hold on to
+    static VALUES: Mutex<Vec<(String, String, Option<CString>)>> = Mutex::new(Vec::new());
+
+    let Some(config) = crate::CONFIG.get() else {
+        return ptr::null();
+    };
+
+    if section.is_null() || key.is_null() {
+        return ptr::null();
+    }
+
+    let (Ok(section), Ok(key)) = (unsafe { CStr::from_ptr(section) }.to_str(), unsafe { CStr::from_ptr(key) }.to_str()) else {
+        return ptr::null();
+    };
+
+    let mut values = VALUES.lock().unwrap();
+    if let Some((_, _, value)) = values.iter().find(|(s, k, _)| s == section && k == key) {
+        return as_ptr(value.as_ref());
+    }
+
+    let value = config.get(section, key).and_then(|value| CString::new(value).ok());
+    let pointer = as_ptr(value.as_ref());
+    values.push((section.to_string(), key.to_string(), value));
+    pointer
+}
+
+/// Writes `message` to doorstop's log, `level` goes from `1` (error) to `5` (trace), anything else is ignored.
+///
+/// # Safety
+/// `message` has to be `null` or a nul-terminated string, invalid UTF-8 is replaced.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn doorstop_log(level: u32, message: *const c_char) {
+    let level = match level {
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        5 => Level::Trace,
+        _ => return,
+    };
+
+    if message.is_null() {
+        return;
+    }
+
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    log::log!(target: "managed", level, "{message}");
+}
+
+/// Lets the game continue when it waits on an asynchronous entrypoint (see `entrypoint_ready_timeout`), does nothing otherwise.
+#[unsafe(no_mangle)]
+pub extern "C" fn doorstop_signal_ready() {
+    crate::runtimes::signal_ready();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_info_fills_only_the_callers_prefix() {
+        #[repr(C)]
+        struct DoorstopInfoPrefix {
+            size: usize,
+            api_version: u32,
+            runtime: u32,
+            canary: u64,
+        }
+
+        let mut info = DoorstopInfoPrefix {
+            size: size_of::<usize>() + size_of::<u32>() * 2,
+            api_version: 0,
+            runtime: u32::MAX,
+            canary: u64::MAX,
+        };
+
+        assert!(unsafe { doorstop_get_info((&raw mut info).cast()) });
+        assert_eq!(info.api_version, DOORSTOP_API_VERSION);
+        assert_eq!(info.runtime, RuntimeKind::Unknown as u32);
+        assert_eq!(info.canary, u64::MAX);
+
+        let mut too_small = 0usize;
+        assert!(!unsafe { doorstop_get_info((&raw mut too_small).cast()) });
+    }
+}
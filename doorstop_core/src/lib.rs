@@ -1,66 +1,146 @@
 #![feature(extern_types)]
 #![feature(cstr_display)]
 #![feature(drop_guard)]
+#![feature(once_cell_try)]
+#![feature(thread_id_value)]
 
-mod config;
+pub mod api;
 mod patches;
+mod plugins;
 mod runtimes;
 mod utils;
 
 use std::{
     env,
     ffi::c_void,
+    fmt::Write as _,
     fs,
     io::Write,
+    panic,
     path::{Path, PathBuf},
     process,
     process::exit,
     str::FromStr,
-    sync::{
-        OnceLock,
-        atomic::{AtomicBool, Ordering},
-    },
+    sync::OnceLock,
+    thread,
+    time::SystemTime,
 };
 
 use anyhow::Context;
 use cfg_if::cfg_if;
+use doorstop_config::{Config, LoadContext, migrate_config_file};
 use fern::colors::{Color, ColoredLevelConfig};
 use log::{LevelFilter, error, info, log_enabled, trace, warn};
 use plthook::ObjectFile;
 
-use crate::{
-    config::Config,
-    utils::{lazy_file_writer::LazyFileWriter, process_lock::ensure_single_instance},
+pub use crate::utils::arguments::rewrite_c_arguments;
+use crate::utils::{
+    async_writer::{AsyncWriter, flush_logs},
+    json_log,
+    lazy_file_writer::LazyFileWriter,
+    log_files::{expand_log_path, has_session_placeholders, prune_logs},
+    process_lock::ensure_single_instance,
+    time::UtcTime,
 };
 
 static CONFIG: OnceLock<Config> = OnceLock::new();
 
+/// Target of the printed config, always logged at info level.
+const CONFIG_LOG_TARGET: &str = "doorstop::config";
+
 pub(crate) fn get_config() -> &'static Config {
     CONFIG.get().unwrap()
 }
 
-fn fix_cwd() -> anyhow::Result<()> {
-    if env::var("DOORSTOP_PLAYER").is_ok() {
-        return Ok(());
+/// Returns the path of the game executable (or its app bundle on macOS).
+fn get_application_path() -> anyhow::Result<PathBuf> {
+    // doorstop_player resolves the real game executable for us
+    if env::var("DOORSTOP_PLAYER").is_ok()
+        && let Some(path) = env::var_os("DOORSTOP_PLAYER_EXECUTABLE")
+    {
+        return Ok(PathBuf::from(path));
     }
 
-    let application_path = {
-        cfg_if! {
-            if #[cfg(target_os = "macos")] {
-                use objc2_foundation::NSBundle;
+    cfg_if! {
+        if #[cfg(target_os = "macos")] {
+            use objc2_foundation::NSBundle;
 
-                let bundle = NSBundle::mainBundle();
+            let bundle = NSBundle::mainBundle();
 
-                PathBuf::from(bundle.bundlePath().to_string())
-            } else {
-                env::current_exe()?
-            }
+            Ok(PathBuf::from(bundle.bundlePath().to_string()))
+        } else {
+            Ok(env::current_exe()?)
         }
+    }
+}
+
+fn get_game_dir() -> anyhow::Result<PathBuf> {
+    let application_path = get_application_path()?;
+    Ok(application_path.parent().unwrap().to_path_buf())
+}
+
+pub(crate) fn get_executable_name() -> anyhow::Result<String> {
+    let application_path = get_application_path()?;
+    Ok(application_path.file_stem().unwrap().to_string_lossy().into_owned())
+}
+
+/// Removes the `--doorstop-*` arguments and their values if `strip_arguments` is enabled, otherwise returns `args` as is.
+pub fn strip_arguments<T>(args: Vec<T>, to_string: impl Fn(&T) -> String) -> Vec<T> {
+    let Some(config) = CONFIG.get().filter(|config| config.strip_arguments) else {
+        return args;
     };
 
-    let application_folder = application_path.parent().unwrap();
+    let names: Vec<String> = args.iter().map(to_string).collect();
+    let is_doorstop_argument = config.find_doorstop_arguments(&names);
 
-    env::set_current_dir(application_folder)?;
+    args.into_iter()
+        .zip(is_doorstop_argument)
+        .filter_map(|(arg, is_doorstop_argument)| (!is_doorstop_argument).then_some(arg))
+        .collect()
+}
+
+/// Adds `extra_args_prepend` right after the program name and `extra_args` at the end, unless doorstop is disabled.
+pub fn add_extra_arguments<T>(mut args: Vec<T>, from_string: impl Fn(&str) -> T) -> Vec<T> {
+    let Some(config) = CONFIG.get().filter(|config| config.enabled) else {
+        return args;
+    };
+
+    let index = args.len().min(1);
+    args.splice(index..index, config.extra_args_prepend.iter().map(|arg| from_string(arg)));
+    args.extend(config.extra_args.iter().map(|arg| from_string(arg)));
+    args
+}
+
+/// Returns the process' command line, without the `--doorstop-*` arguments if `strip_arguments` is enabled and with the extra arguments added.
+#[cfg(windows)]
+pub fn get_command_line() -> windows::core::PWSTR {
+    use windows::{Win32::System::Environment::GetCommandLineW, core::PWSTR};
+
+    static COMMAND_LINE: OnceLock<Vec<u16>> = OnceLock::new();
+
+    let command_line = COMMAND_LINE.get_or_init(|| unsafe { utils::arguments::rewrite_command_line(GetCommandLineW().as_wide(), false) });
+    PWSTR(command_line.as_ptr().cast_mut())
+}
+
+/// Applies the `[Environment]` section to the process, so Unity and the runtimes see it too.
+fn apply_environment(config: &Config) {
+    for environment in &config.environment {
+        if let Some(value) = environment.value.as_ref() {
+            trace!("Setting environment variable {}={value} ({})", environment.name, environment.source);
+            unsafe { env::set_var(&environment.name, value) }
+        } else {
+            trace!("Unsetting environment variable {} ({})", environment.name, environment.source);
+            unsafe { env::remove_var(&environment.name) }
+        }
+    }
+}
+
+fn fix_cwd() -> anyhow::Result<()> {
+    if env::var("DOORSTOP_PLAYER").is_ok() {
+        return Ok(());
+    }
+
+    env::set_current_dir(get_game_dir()?)?;
 
     Ok(())
 }
@@ -103,6 +183,7 @@ pub fn fatal<T, E: std::fmt::Debug + std::fmt::Display>(result: Result<T, E>) ->
     result.unwrap_or_else(|err| {
         if log_enabled!(log::Level::Error) {
             error!("{err:?}");
+            flush_logs();
         } else {
             eprintln!("[doorstop] {err:?}");
         }
@@ -134,26 +215,30 @@ pub unsafe fn try_init(unity_player_handle: *const c_void) -> anyhow::Result<()>
         }
     }
 
-    let config = CONFIG.get_or_init(Config::load);
-
-    setup_logging().context("Failed to setup logging")?;
-
-    if unity_player_handle.is_null() {
+    if unity_player_handle.is_null() && find_data_folder().is_none() {
         // In case there is no UnityPlayer, it could still be an old Unity version where it was compiled into the executable
         // Do a simple heuristic check by looking for a valid data folder
-        if find_data_folder().is_none() {
-            trace!(
-                "Current process ({} - {}) is not an Unity game, skipping",
-                process::id(),
-                env::current_exe()?.display()
-            );
-            return Ok(());
-        }
+        setup_logging(None).context("Failed to setup logging")?;
+        trace!(
+            "Current process ({} - {}) is not an Unity game, skipping",
+            process::id(),
+            env::current_exe()?.display()
+        );
+        return Ok(());
+    }
 
-        FILE_LOGGING.store(true, Ordering::Relaxed);
+    let config = CONFIG
+        .get_or_try_init(|| {
+            let context = LoadContext::from_process(get_game_dir()?, get_executable_name()?, find_data_folder())?;
+            Config::load(&context)
+        })
+        .context("Failed to load config")?;
+
+    setup_logging(Some(config)).context("Failed to setup logging")?;
+
+    if unity_player_handle.is_null() {
         info!("UnityPlayer not found, hooking into main executable instead");
     } else {
-        FILE_LOGGING.store(true, Ordering::Relaxed);
         info!("UnityPlayer found, initializing");
     }
 
@@ -162,10 +247,62 @@ pub unsafe fn try_init(unity_player_handle: *const c_void) -> anyhow::Result<()>
         return Ok(());
     }
 
+    if let Some(file_path) = config.file_path.as_ref() {
+        info!("Using config file {}", file_path.display());
+    } else if config.fragment_paths.is_empty() {
+        info!("No config file found, using defaults");
+    }
+
+    for fragment_path in &config.fragment_paths {
+        info!("Merged config file {}", fragment_path.display());
+    }
+
+    if config.migrate
+        && let Some(file_path) = config.file_path.as_ref()
+    {
+        if migrate_config_file(file_path).context("Failed to migrate config file")? {
+            info!(
+                "Migrated config file {}, the original was kept as {}.bak",
+                file_path.display(),
+                file_path.display()
+            );
+        } else {
+            info!("Config file {} is already up to date", file_path.display());
+        }
+    }
+
+    // Printed before the diagnostics, so an invalid config in strict mode still shows up
+    if config.print {
+        let dump = config.dump();
+        _ = writeln!(std::io::stdout(), "{dump}");
+        info!(target: CONFIG_LOG_TARGET, "{dump}");
+    }
+
+    if config.print_exit {
+        let result = config.report_diagnostics();
+        if let Err(e) = &result {
+            error!("{e:?}");
+        }
+
+        flush_logs();
+        exit(i32::from(result.is_err()));
+    }
+
+    config.report_diagnostics()?;
+
     trace!("config = {config:?}");
 
+    if !config.enabled {
+        info!("Doorstop is disabled ({}), skipping", config.describe_source("General", "enabled"));
+        return Ok(());
+    }
+
+    apply_environment(config);
+
     fix_cwd().context("Failed to fix current working directory")?;
 
+    plugins::load_plugins()?;
+
     unsafe {
         let object = if unity_player_handle.is_null() {
             ObjectFile::open_main_program()?
@@ -180,7 +317,18 @@ pub unsafe fn try_init(unity_player_handle: *const c_void) -> anyhow::Result<()>
 }
 
 fn find_data_folder() -> Option<PathBuf> {
-    if let Ok(current_exe) = env::current_exe()
+    let current_exe = {
+        cfg_if! {
+            if #[cfg(target_os = "macos")] {
+                env::current_exe().ok()
+            } else {
+                // Resolves to the game executable under doorstop_player
+                get_application_path().ok()
+            }
+        }
+    };
+
+    if let Some(current_exe) = current_exe
         && let Some(current_exe_dir) = current_exe.parent()
     {
         let paths = {
@@ -219,44 +367,120 @@ fn is_valid_data_folder(path: &Path) -> bool {
     path.is_dir() && ["data.unity3d", "globalgamemanagers", "mainData"].iter().any(|file| path.join(file).exists())
 }
 
-static FILE_LOGGING: AtomicBool = AtomicBool::new(false);
+/// Sets up the console and file loggers from the `[Logging]` section.
+/// Without a config, in processes that aren't Unity games, only `DOORSTOP_LOG_LEVEL` is used and nothing is written to the log file.
+fn setup_logging(config: Option<&Config>) -> anyhow::Result<()> {
+    let defaults = Config {
+        log_level: env::var("DOORSTOP_LOG_LEVEL").ok(),
+        log_file_enabled: false,
+        ..Config::default()
+    };
+    let config = config.unwrap_or(&defaults);
+
+    let mut dispatch = fern::Dispatch::new().level(LevelFilter::Info);
 
-fn setup_logging() -> anyhow::Result<()> {
-    let log_level = if let Ok(level) = env::var("DOORSTOP_LOG_LEVEL") {
-        LevelFilter::from_str(&level)?
-    } else {
-        LevelFilter::Info
+    for directive in config
+        .log_level
+        .iter()
+        .flat_map(|level| level.split(','))
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+    {
+        dispatch = if let Some((target, level)) = directive.split_once('=') {
+            dispatch.level_for(target.trim().to_string(), parse_level_filter(level)?)
+        } else {
+            dispatch.level(parse_level_filter(directive)?)
+        };
+    }
+
+    if config.print {
+        dispatch = dispatch.level_for(CONFIG_LOG_TARGET, LevelFilter::Info);
+    }
+
+    let (timestamps, thread_ids) = (config.log_timestamps, config.log_thread_ids);
+    let prefix = move || {
+        let mut prefix = String::new();
+        if timestamps {
+            _ = write!(prefix, "{} ", UtcTime::from(SystemTime::now()));
+        }
+        if thread_ids {
+            _ = write!(prefix, "#{} ", thread::current().id().as_u64());
+        }
+        prefix
     };
 
-    let colors_line = ColoredLevelConfig::new()
-        .error(Color::Red)
-        .warn(Color::Yellow)
-        .info(Color::White)
-        .debug(Color::BrightBlack)
-        .trace(Color::BrightBlack);
+    if config.log_console_enabled {
+        let colors_line = ColoredLevelConfig::new()
+            .error(Color::Red)
+            .warn(Color::Yellow)
+            .info(Color::White)
+            .debug(Color::BrightBlack)
+            .trace(Color::BrightBlack);
 
-    fern::Dispatch::new()
-        .level(log_level)
-        .chain(
+        dispatch = dispatch.chain(
             fern::Dispatch::new()
                 .format(move |out, message, record| {
                     out.finish(format_args!(
-                        "{color_line}[{level} {target}{color_line}] {message}\x1B[0m",
+                        "{color_line}[{prefix}{level} {target}{color_line}] {message}\x1B[0m",
                         color_line = format_args!("\x1B[{}m", colors_line.get_color(&record.level()).to_fg_str()),
+                        prefix = prefix(),
                         target = record.target(),
                         level = record.level(),
                         message = message,
                     ));
                 })
                 .chain(std::io::stderr()),
-        )
-        .chain(
+        );
+    }
+
+    if config.log_file_enabled {
+        let template = config.log_file.as_deref().unwrap_or(Path::new("doorstop.log"));
+        let writer = log_file_writer(template, config)?;
+
+        dispatch = dispatch.chain(
+            fern::Dispatch::new()
+                .format(move |out, message, record| out.finish(format_args!("[{}{} {}] {}", prefix(), record.level(), record.target(), message)))
+                .chain(Box::new(writer) as Box<dyn Write + Send>),
+        );
+    }
+
+    if let Some(template) = config.log_json_file.as_deref() {
+        let writer = log_file_writer(template, config)?;
+
+        dispatch = dispatch.chain(
             fern::Dispatch::new()
-                .filter(|_| FILE_LOGGING.load(Ordering::Relaxed))
-                .format(|out, message, record| out.finish(format_args!("[{} {}] {}", record.level(), record.target(), message)))
-                .chain(Box::new(LazyFileWriter::new("doorstop.log")) as Box<dyn Write + Send>),
-        )
-        .apply()?;
+                .format(|out, message, record| out.finish(format_args!("{}", json_log::format_record(message, record))))
+                .chain(Box::new(writer) as Box<dyn Write + Send>),
+        );
+    }
+
+    dispatch.apply()?;
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        error!("{info}");
+        flush_logs();
+        default_hook(info);
+    }));
 
     Ok(())
 }
+
+fn log_file_writer(template: &Path, config: &Config) -> anyhow::Result<AsyncWriter> {
+    let executable_name = get_executable_name()?;
+
+    // Logs with a different name every session are pruned up front, the others are rotated once the file is locked
+    let keep = if has_session_placeholders(template) {
+        _ = prune_logs(template, &executable_name, config.log_keep);
+        1
+    } else {
+        config.log_keep
+    };
+
+    let writer = LazyFileWriter::new(expand_log_path(template, &executable_name), keep, config.log_max_size as u64);
+    Ok(AsyncWriter::new(writer)?)
+}
+
+fn parse_level_filter(level: &str) -> anyhow::Result<LevelFilter> {
+    LevelFilter::from_str(level.trim()).with_context(|| format!("Invalid log level `{}`", level.trim()))
+}
@@ -0,0 +1,85 @@
+use std::env;
+
+use log::trace;
+use plthook::ObjectFile;
+
+use crate::{get_config, plt_hook};
+
+#[cfg_attr(unix, allow(unused_variables))]
+pub(super) fn patch(object: &ObjectFile) -> anyhow::Result<()> {
+    if !get_config().rewrites_arguments() {
+        return Ok(());
+    }
+
+    // One argument per line, managed code can't get to the original ones otherwise
+    let original_arguments: Vec<String> = env::args_os().map(|arg| arg.to_string_lossy().into_owned()).collect();
+    unsafe { env::set_var("DOORSTOP_ORIGINAL_ARGUMENTS", original_arguments.join("\n")) };
+
+    // The main program is doorstop_player itself when running under it, which passes the rewritten arguments on its own
+    let main_program = ObjectFile::open_main_program()?;
+
+    #[cfg(windows)]
+    {
+        use std::sync::OnceLock;
+
+        use log::warn;
+        use windows::{
+            Win32::{Foundation::HINSTANCE, System::Environment::GetCommandLineW},
+            core::PWSTR,
+        };
+
+        use crate::{get_command_line, utils::arguments::rewrite_command_line};
+
+        static ARGUMENTS: OnceLock<Vec<u16>> = OnceLock::new();
+
+        if let Err(e) = plt_hook!(object, "GetCommandLineW", extern "system" fn(_orig) -> PWSTR, { get_command_line() }) {
+            warn!("Couldn't hook GetCommandLineW: {e}");
+        }
+
+        if let Err(e) = plt_hook!(
+            &main_program,
+            "UnityMain",
+            extern "system" fn(orig, h_instance: HINSTANCE, h_prev_instance: HINSTANCE, _lp_cmd_line: PWSTR, n_show_cmd: i32) -> i32,
+            {
+                // lpCmdLine doesn't include the program name
+                let arguments = ARGUMENTS.get_or_init(|| unsafe { rewrite_command_line(GetCommandLineW().as_wide(), true) });
+                unsafe { orig(h_instance, h_prev_instance, PWSTR(arguments.as_ptr().cast_mut()), n_show_cmd) }
+            }
+        ) {
+            trace!("Couldn't hook UnityMain in the main program: {e}");
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::ffi::c_char;
+
+        use crate::utils::arguments::rewrite_c_arguments;
+
+        const PLAYER_MAIN_SYMBOL: &str = {
+            cfg_if::cfg_if! {
+                if #[cfg(target_os = "macos")] {
+                    "_Z10PlayerMainiPPKc" // PlayerMain(int, char const**)
+                } else {
+                    "_Z10PlayerMainiPPc" // PlayerMain(int, char**)
+                }
+            }
+        };
+
+        if let Err(e) = plt_hook!(
+            &main_program,
+            PLAYER_MAIN_SYMBOL,
+            extern "system" fn(orig, argc: i32, argv: *const *const c_char) -> i32,
+            {
+                unsafe {
+                    let (argc, argv) = rewrite_c_arguments(argc, argv);
+                    orig(argc, argv)
+                }
+            }
+        ) {
+            trace!("Couldn't hook PlayerMain in the main program: {e}");
+        }
+    }
+
+    Ok(())
+}
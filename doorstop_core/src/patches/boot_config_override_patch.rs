@@ -11,7 +11,7 @@ fn try_override(path: &Path) -> Option<&PathBuf> {
         && file_name == "boot.config"
         && let Some(new_path) = get_config().boot_config_override.as_ref()
     {
-        info!("Overriding boot.config to {}", new_path.display());
+        info!(path:% = new_path.display(); "Overriding boot.config to {}", new_path.display());
         return Some(new_path);
     }
 
@@ -0,0 +1,40 @@
+use std::ffi::c_int;
+
+use log::trace;
+use plthook::{ErrorKind, ObjectFile};
+
+use crate::{get_config, plt_hook, runtimes};
+
+/// Invokes the stop methods when `UnityPlayer` or the game executable calls `exit`, while the runtime and its threads are still alive.
+/// Other modules calling `exit` are only covered by the runtimes' `atexit` handler, which isn't registered on Windows.
+pub(super) fn patch(object: &ObjectFile) -> anyhow::Result<()> {
+    if get_config().stop_method.is_none() {
+        return Ok(());
+    }
+
+    ignore_not_imported(plt_hook!(object, "exit", extern "C" fn(orig, status: c_int), {
+        trace!("exit({status})");
+        runtimes::stop();
+        unsafe { orig(status) }
+    }))?;
+
+    // Without a separate UnityPlayer this is the same module as above, the second hook just calls into the first one
+    let main_program = ObjectFile::open_main_program()?;
+    ignore_not_imported(plt_hook!(&main_program, "exit", extern "C" fn(orig, status: c_int), {
+        trace!("exit({status}) from the game executable");
+        runtimes::stop();
+        unsafe { orig(status) }
+    }))?;
+
+    Ok(())
+}
+
+fn ignore_not_imported(result: plthook::Result<()>) -> plthook::Result<()> {
+    match result {
+        Err(e) if matches!(e.kind(), ErrorKind::FunctionNotFound) => {
+            trace!("exit isn't imported");
+            Ok(())
+        }
+        result => result,
+    }
+}
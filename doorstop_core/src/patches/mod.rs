@@ -1,10 +1,13 @@
+mod arguments_patch;
 mod boot_config_override_patch;
 mod disable_console_redirect_patch;
+mod exit_patch;
 mod mono_override_patch;
+mod output_log_patch;
 
 use std::{
     env,
-    ffi::{CStr, c_char, c_void},
+    ffi::{CStr, OsString, c_char, c_void},
 };
 
 use log::trace;
@@ -16,19 +19,33 @@ use crate::{
 };
 
 pub unsafe fn patch(object: &ObjectFile) -> anyhow::Result<()> {
-    if get_config().redirect_output_log {
-        unsafe { env::set_var("UNITY_LOG_FILE", "output_log.txt") }
-    }
+    output_log_patch::patch()?;
 
     boot_config_override_patch::patch(object)?;
     disable_console_redirect_patch::patch(object)?;
     mono_override_patch::patch(object)?;
+    arguments_patch::patch(object)?;
+    exit_patch::patch(object)?;
 
     unsafe {
         env::set_var("DOORSTOP_INITIALIZED", "TRUE");
         env::set_var("DOORSTOP_PROCESS_PATH", env::current_exe()?);
-        if let Some(target_assembly) = get_config().target_assembly.as_ref() {
-            env::set_var("DOORSTOP_INVOKE_DLL_PATH", target_assembly);
+        if let Some(config_path) = get_config().file_path.as_ref() {
+            env::set_var("DOORSTOP_CONFIG_PATH", config_path);
+        }
+        if let Some(target) = get_config().all_targets().next() {
+            // Loaders find their own assembly through this one, so it stays a single path
+            env::set_var("DOORSTOP_INVOKE_DLL_PATH", target.path);
+
+            // Separated by `;` like every other doorstop list, env::join_paths would use `:` on unix
+            let mut invoke_dll_paths = OsString::new();
+            for target in get_config().all_targets() {
+                if !invoke_dll_paths.is_empty() {
+                    invoke_dll_paths.push(";");
+                }
+                invoke_dll_paths.push(target.path);
+            }
+            env::set_var("DOORSTOP_INVOKE_DLL_PATHS", invoke_dll_paths);
         }
     }
 
@@ -50,7 +67,7 @@ pub unsafe fn patch(object: &ObjectFile) -> anyhow::Result<()> {
             warn!("libmono is linked directly");
 
             if let Some(mono_override_path) = get_config().mono_override.as_ref() {
-                info!("Overriding mono to {}", mono_override_path.display());
+                info!(path:% = mono_override_path.display(); "Overriding mono to {}", mono_override_path.display());
                 libc::dlclose(libmono_handle);
                 libmono_handle = libloading::os::unix::Library::new(mono_override_path)?.into_raw();
             }
@@ -70,7 +87,7 @@ pub unsafe fn patch(object: &ObjectFile) -> anyhow::Result<()> {
                 }
 
                 if let Some(address) = mono::try_hook(libmono_handle, name, address) {
-                    trace!("Hooking {name}");
+                    trace!(symbol = name; "Hooking {name}");
                     object.replace(name, address)?.discard();
                 } else if get_config().mono_override.is_some() {
                     object.replace(name, address)?.discard();
@@ -111,7 +128,7 @@ pub unsafe fn patch(object: &ObjectFile) -> anyhow::Result<()> {
             if let Ok(name) = name.to_str()
                 && let Some(address) = Option::or(mono::try_hook(module, name, address), il2cpp::try_hook(module, name, address))
             {
-                trace!("Hooking {name}");
+                trace!(symbol = name; "Hooking {name}");
                 return address;
             }
 
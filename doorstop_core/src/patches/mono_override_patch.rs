@@ -23,7 +23,7 @@ pub(super) fn patch(object: &ObjectFile) -> anyhow::Result<()> {
                     if let Some(file_name) = path.file_name()
                         && (file_name == "mono-2.0-bdwgc.dll" || file_name == "mono.dll")
                     {
-                        info!("Overriding {} to {}", file_name.display(), mono_override_path.display());
+                        info!(path:% = mono_override_path.display(); "Overriding {} to {}", file_name.display(), mono_override_path.display());
                         let new_path = mono_override_path.to_wide();
                         let result = unsafe { orig(PCWSTR::from_raw(new_path.as_ptr())) };
                         if result.is_invalid() {
@@ -68,7 +68,7 @@ pub(super) fn patch(object: &ObjectFile) -> anyhow::Result<()> {
                                 .and_then(|s| s.strip_suffix(env::consts::DLL_SUFFIX))
                             && (library_name == "monobdwgc-2.0" || library_name == "mono" || library_name == "mono.0")
                         {
-                            info!("Overriding {} to {}", file_name, mono_override_path.display());
+                            info!(path:% = mono_override_path.display(); "Overriding {} to {}", file_name, mono_override_path.display());
                             let new_path = mono_override_path.to_cstr().unwrap();
 
                             let result = unsafe { orig(new_path.as_ptr(), flags) };
@@ -0,0 +1,61 @@
+use std::{env, fs, fs::File, path::PathBuf};
+
+use anyhow::Context;
+use doorstop_config::variable_name_eq;
+use log::{info, warn};
+
+use crate::{
+    get_config, get_executable_name,
+    utils::{
+        log_files::{expand_log_path, prune_logs},
+        log_follower::follow_to_stderr,
+    },
+};
+
+/// Points Unity's log at `output_log_path` (or `output_log.txt` with `redirect_output_log`) and copies it to stderr if `output_log_to_stderr` is set.
+pub(super) fn patch() -> anyhow::Result<()> {
+    let config = get_config();
+
+    // An explicit UNITY_LOG_FILE from the [Environment] section takes precedence
+    let path = if let Some(environment) = config
+        .environment
+        .iter()
+        .find(|environment| variable_name_eq(&environment.name, "UNITY_LOG_FILE"))
+    {
+        let Some(path) = environment.value.as_ref() else {
+            return Ok(());
+        };
+
+        PathBuf::from(path)
+    } else {
+        let template = match config.output_log_path.as_ref() {
+            Some(template) => template.clone(),
+            None if config.redirect_output_log || config.output_log_to_stderr => PathBuf::from("output_log.txt"),
+            None => return Ok(()),
+        };
+
+        let executable_name = get_executable_name()?;
+
+        if let Err(e) = prune_logs(&template, &executable_name, config.output_log_keep) {
+            warn!("Failed to clean up old Unity logs: {e}");
+        }
+
+        let path = expand_log_path(&template, &executable_name);
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        info!("Redirecting Unity's log to {}", path.display());
+        unsafe { env::set_var("UNITY_LOG_FILE", &path) }
+
+        path
+    };
+
+    if config.output_log_to_stderr {
+        // Don't copy what's left over from the previous session
+        File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        follow_to_stderr(path).context("Failed to start copying Unity's log to stderr")?;
+    }
+
+    Ok(())
+}
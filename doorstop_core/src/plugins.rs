@@ -0,0 +1,81 @@
+use std::{
+    ffi::c_int,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use log::info;
+
+use crate::{
+    api::{DOORSTOP_API, DoorstopApi},
+    get_config,
+};
+
+/// `int doorstop_plugin_init(const DoorstopApi* api)`, a non-zero return value fails the initialization.
+type PluginInit = unsafe extern "C" fn(api: *const DoorstopApi) -> c_int;
+
+const LIBRARY_EXTENSION: &str = {
+    #[cfg(windows)]
+    {
+        "dll"
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        "dylib"
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        "so"
+    }
+};
+
+/// Loads `native_plugins` followed by the libraries in `native_plugins_dir` and calls their `doorstop_plugin_init`.
+pub(crate) fn load_plugins() -> anyhow::Result<()> {
+    let config = get_config();
+
+    let mut paths = config.native_plugins.clone();
+    if let Some(dir) = config.native_plugins_dir.as_ref() {
+        paths.extend(find_libraries(dir).with_context(|| format!("Failed to list native plugins in {}", dir.display()))?);
+    }
+
+    for path in paths {
+        unsafe { load_plugin(&path) }.with_context(|| format!("Failed to load native plugin {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn find_libraries(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == LIBRARY_EXTENSION) {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+unsafe fn load_plugin(path: &Path) -> anyhow::Result<()> {
+    unsafe {
+        info!(path:% = path.display(); "Loading native plugin {}", path.display());
+
+        let library = libloading::Library::new(path)?;
+        let init = *library.get::<PluginInit>(c"doorstop_plugin_init".to_bytes_with_nul())?;
+
+        // Plugins stay loaded for as long as the process runs
+        std::mem::forget(library);
+
+        let result = init(&raw const DOORSTOP_API);
+        if result != 0 {
+            bail!("doorstop_plugin_init returned {result}");
+        }
+
+        Ok(())
+    }
+}
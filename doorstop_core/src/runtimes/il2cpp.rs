@@ -1,18 +1,28 @@
 use std::{
     env,
     ffi::{CStr, CString, c_char, c_void},
-    fs, mem, ptr,
+    fs, iter, mem,
+    path::PathBuf,
+    ptr,
     str::FromStr,
+    sync::Mutex,
 };
 
 use anyhow::{Context, bail};
 use const_format::{concatcp, formatc};
+use doorstop_config::Target;
 use doorstop_shared::OsStrExt;
 use log::{error, warn};
 
 use crate::{
-    fatal, get_config, hook_fn,
-    utils::bindings::{BindingsStruct, bindings},
+    api,
+    api::RuntimeKind,
+    fatal, get_config, hook_fn, runtimes,
+    runtimes::{get_entrypoint, get_stop_method, invoke_targets, run_entrypoints},
+    utils::{
+        arguments::{strip_c_arguments, strip_utf16_arguments},
+        bindings::{BindingsStruct, bindings},
+    },
 };
 
 // TODO is system stdcall here correct? (check if its stdcall on win-x86)
@@ -42,6 +52,8 @@ bindings! {
     }
 }
 
+static STOP_DELEGATES: Mutex<Vec<unsafe extern "system" fn()>> = Mutex::new(Vec::new());
+
 pub fn try_hook(_module: *mut c_void, name: &str, address: *const c_void) -> Option<*const c_void> {
     match name {
         "il2cpp_init" => Some(hook_fn!(address, extern "C" fn(orig, domain_name: *const c_char) -> i32, {
@@ -50,6 +62,32 @@ pub fn try_hook(_module: *mut c_void, name: &str, address: *const c_void) -> Opt
             result
         }) as *const _),
 
+        // Called by Unity on quit, CoreCLR itself is never shut down but the game's objects are about to be
+        "il2cpp_shutdown" => Some(hook_fn!(address, extern "C" fn(orig), {
+            runtimes::stop();
+            unsafe { orig() };
+            runtimes::runtime_shut_down();
+        }) as *const _),
+
+        // Environment.GetCommandLineArgs
+        "il2cpp_set_commandline_arguments" => Some(
+            hook_fn!(address, extern "C" fn(orig, argc: i32, argv: *const *const c_char, basedir: *const c_char), {
+                unsafe {
+                    let (argc, argv) = strip_c_arguments(argc, argv);
+                    orig(argc, argv, basedir);
+                }
+            }) as *const _,
+        ),
+
+        "il2cpp_set_commandline_arguments_utf16" => Some(
+            hook_fn!(address, extern "C" fn(orig, argc: i32, argv: *const *const u16, basedir: *const c_char), {
+                unsafe {
+                    let (argc, argv) = strip_utf16_arguments(argc, argv);
+                    orig(argc, argv, basedir);
+                }
+            }) as *const _,
+        ),
+
         _ => None,
     }
 }
@@ -63,10 +101,10 @@ fn bootstrap() -> anyhow::Result<()> {
     unsafe {
         let config = get_config();
 
-        let Some(target_assembly) = config.target_assembly.as_ref() else {
+        if config.all_targets().next().is_none() {
             warn!("No target assembly specified, skipping bootstrap");
             return Ok(());
-        };
+        }
 
         let coreclr_path = config
             .clr_corlib_dir
@@ -86,11 +124,18 @@ fn bootstrap() -> anyhow::Result<()> {
             coreclr_set_error_writer(error_writer_callback);
         }
 
-        let target_dir = target_assembly.parent().unwrap();
-        let app_paths = env::join_paths([clr_corlib_dir, target_dir])?;
+        let mut target_dirs: Vec<PathBuf> = Vec::new();
+        for target in config.all_targets() {
+            let target_dir = target.path.parent().unwrap().to_path_buf();
+            if !target_dirs.contains(&target_dir) {
+                target_dirs.push(target_dir);
+            }
+        }
+
+        let app_paths = env::join_paths(iter::once(clr_corlib_dir.as_path()).chain(target_dirs.iter().map(PathBuf::as_path)))?;
         let app_paths_cstr = app_paths.to_cstr().unwrap();
 
-        let native_paths = env::join_paths([target_dir.join(formatc!("runtimes/{DOTNET_RID}/native"))])?;
+        let native_paths = env::join_paths(target_dirs.iter().map(|target_dir| target_dir.join(formatc!("runtimes/{DOTNET_RID}/native"))))?;
         let native_paths_cstr = native_paths.to_cstr().unwrap();
 
         let mut property_keys: Vec<*const c_char> = vec![];
@@ -119,35 +164,101 @@ fn bootstrap() -> anyhow::Result<()> {
             bail!("Failed to initialize CoreCLR ({result:X})");
         }
 
-        let target_assembly_name = CString::from_str(target_assembly.file_stem().unwrap().to_str().unwrap())?;
-        if !fs::exists(target_assembly)? {
+        // CoreCLR can't be unloaded once initialized
+        mem::forget(lib);
+
+        env::set_var("DOORSTOP_MANAGED_FOLDER_DIR", clr_corlib_dir);
+        api::set_runtime(RuntimeKind::CoreClr, clr_corlib_dir);
+        env::set_var("DOORSTOP_DLL_SEARCH_DIRS", app_paths);
+
+        // CoreCLR attaches native threads by itself when they call a delegate
+        let host = HostHandle(host);
+        run_entrypoints(move || invoke_targets(|target| invoke_target(&coreclr, host.get(), domain_id, target)))
+    }
+}
+
+/// `coreclr_initialize`'s host handle, usable from any thread.
+#[derive(Clone, Copy)]
+struct HostHandle(*const c_void);
+
+unsafe impl Send for HostHandle {}
+
+impl HostHandle {
+    fn get(self) -> *const c_void {
+        self.0
+    }
+}
+
+unsafe fn invoke_target(coreclr: &CoreCLR, host: *const c_void, domain_id: u32, target: &Target) -> anyhow::Result<()> {
+    unsafe {
+        let target_assembly_name = CString::from_str(target.path.file_stem().unwrap().to_str().unwrap())?;
+        if !fs::exists(&target.path)? {
             bail!("Failed to load target assembly");
         }
 
-        let mut startup: Option<unsafe extern "system" fn()> = None;
+        let (type_name, method_name) = get_entrypoint(target)?;
+        let type_name = CString::new(type_name)?;
+        let method_name = CString::new(method_name)?;
+
+        let mut startup: *const c_void = ptr::null();
         let result = (coreclr.coreclr_create_delegate)(
             host,
             domain_id,
             target_assembly_name.as_ptr(),
-            c"Doorstop.Entrypoint".as_ptr(),
-            c"Start".as_ptr(),
-            (&raw mut startup).cast(),
+            type_name.as_ptr(),
+            method_name.as_ptr(),
+            &raw mut startup,
         );
         if result != 0 {
-            bail!("Failed to find entrypoint method in target assembly ({result:X})");
+            bail!(
+                "Failed to find entrypoint method {}:{} in target assembly ({result:X})",
+                type_name.display(),
+                method_name.display()
+            );
         }
 
-        env::set_var("DOORSTOP_MANAGED_FOLDER_DIR", clr_corlib_dir);
-        env::set_var("DOORSTOP_DLL_SEARCH_DIRS", app_paths);
+        // The delegate's signature can't be checked, so entrypoint_args are only available through doorstop_get_config
+        let startup: unsafe extern "system" fn() = mem::transmute(startup);
+        startup();
 
-        startup.unwrap()();
+        if let Some((type_name, method_name)) = get_stop_method(target)? {
+            let type_name = CString::new(type_name)?;
+            let method_name = CString::new(method_name)?;
 
-        mem::forget(lib);
+            let mut stop: *const c_void = ptr::null();
+            let result = (coreclr.coreclr_create_delegate)(
+                host,
+                domain_id,
+                target_assembly_name.as_ptr(),
+                type_name.as_ptr(),
+                method_name.as_ptr(),
+                &raw mut stop,
+            );
+            if result == 0 {
+                STOP_DELEGATES
+                    .lock()
+                    .unwrap()
+                    .push(mem::transmute::<*const c_void, unsafe extern "system" fn()>(stop));
+            } else {
+                warn!(
+                    "Failed to find stop method {}:{} in target assembly ({result:X})",
+                    type_name.display(),
+                    method_name.display()
+                );
+            }
+        }
     }
 
     Ok(())
 }
 
+pub(super) fn stop() {
+    for stop in mem::take(&mut *STOP_DELEGATES.lock().unwrap()) {
+        // Exceptions thrown here are unhandled and take the process down, same as in the entrypoint
+        unsafe { stop() };
+    }
+}
+
 const DOTNET_RID: &str = concatcp!(
     {
         #[cfg(windows)]
@@ -1,2 +1,224 @@
+use std::{
+    sync::{
+        Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, bail};
+use doorstop_config::{OnFailure, Target};
+use log::{info, warn};
+
+use crate::{fatal, get_config};
+
 pub mod il2cpp;
 pub mod mono;
+
+/// Splits the target's entrypoint (or the global one) into the type name (including its namespace) and the method name.
+fn get_entrypoint(target: &Target) -> anyhow::Result<(&str, &str)> {
+    let entrypoint = target.entrypoint.as_ref().unwrap_or(&get_config().entrypoint);
+
+    match entrypoint.split_once(':') {
+        Some((type_name, method_name)) if !type_name.is_empty() && !method_name.is_empty() => Ok((type_name, method_name)),
+        _ => bail!("Invalid entrypoint `{entrypoint}`, expected `Namespace.Type:Method`"),
+    }
+}
+
+/// Splits `stop_method` into the type name and the method name, a bare method name is looked up on the type of the target's entrypoint.
+fn get_stop_method(target: &Target) -> anyhow::Result<Option<(&str, &str)>> {
+    let Some(stop_method) = get_config().stop_method.as_deref() else {
+        return Ok(None);
+    };
+
+    match stop_method.split_once(':') {
+        Some((type_name, method_name)) if !type_name.is_empty() && !method_name.is_empty() => Ok(Some((type_name, method_name))),
+        Some(_) => bail!("Invalid stop method `{stop_method}`, expected `Namespace.Type:Method` or `Method`"),
+        None => Ok(Some((get_entrypoint(target)?.0, stop_method))),
+    }
+}
+
+/// Set once a runtime is about to invoke the entrypoints, cleared by the shutdown hooks once it's gone.
+static RUNTIME_ALIVE: AtomicBool = AtomicBool::new(false);
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Invokes the stop methods found while bootstrapping, only the first call while the runtime is alive does anything.
+/// Called before the runtime is shut down or the process exits, a stop method calling `exit` itself won't get here twice.
+pub(crate) fn stop() {
+    if get_config().stop_method.is_none() || !RUNTIME_ALIVE.load(Ordering::Relaxed) || STOPPED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    info!("Invoking stop methods");
+    mono::stop();
+    il2cpp::stop();
+}
+
+fn runtime_started() {
+    RUNTIME_ALIVE.store(true, Ordering::Relaxed);
+
+    // Registered once the runtime is initialized, so this runs before any atexit handler the runtime registered itself.
+    // On Windows a DLL's atexit handlers only run during DLL_PROCESS_DETACH, where calling into the runtime isn't safe, exit is hooked instead.
+    #[cfg(unix)]
+    if get_config().stop_method.is_some() {
+        unsafe { libc::atexit(stop_at_exit) };
+    }
+}
+
+fn runtime_shut_down() {
+    RUNTIME_ALIVE.store(false, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+extern "C" fn stop_at_exit() {
+    stop();
+}
+
+/// Set by the entrypoints (or once they all returned) to let a game waiting on them continue.
+struct Ready {
+    ready: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl Ready {
+    const fn new() -> Self {
+        Self {
+            ready: Mutex::new(false),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn signal(&self) {
+        *self.ready.lock().unwrap() = true;
+        self.changed.notify_all();
+    }
+
+    /// Returns `false` if it wasn't signaled within `timeout`.
+    fn wait(&self, timeout: Duration) -> bool {
+        let (_ready, result) = self.changed.wait_timeout_while(self.ready.lock().unwrap(), timeout, |ready| !*ready).unwrap();
+        !result.timed_out()
+    }
+}
+
+static READY: Ready = Ready::new();
+
+/// Lets the game continue if it's waiting on asynchronous entrypoints, also called once they all returned.
+pub(crate) fn signal_ready() {
+    READY.signal();
+}
+
+/// Runs `invoke` right away, or on a new thread if `entrypoint_async` is set and then waits up to `entrypoint_ready_timeout` for it to signal ready.
+fn run_entrypoints(invoke: impl FnOnce() -> anyhow::Result<()> + Send + 'static) -> anyhow::Result<()> {
+    let config = get_config();
+
+    runtime_started();
+
+    if config.entrypoint_async {
+        info!("Invoking entrypoints asynchronously");
+    }
+
+    spawn_entrypoints(
+        config.entrypoint_async,
+        Duration::from_millis(config.entrypoint_ready_timeout as u64),
+        &READY,
+        invoke,
+    )
+}
+
+fn spawn_entrypoints(
+    is_async: bool,
+    ready_timeout: Duration,
+    ready: &'static Ready,
+    invoke: impl FnOnce() -> anyhow::Result<()> + Send + 'static,
+) -> anyhow::Result<()> {
+    if !is_async {
+        return invoke();
+    }
+
+    thread::Builder::new().name("doorstop-entrypoint".to_string()).spawn(move || {
+        fatal(invoke().context("Failed to invoke entrypoints"));
+        ready.signal();
+    })?;
+
+    if !ready_timeout.is_zero() && !ready.wait(ready_timeout) {
+        warn!("Entrypoints didn't signal ready within {}ms, continuing", ready_timeout.as_millis());
+    }
+
+    Ok(())
+}
+
+/// Invokes every target in order, a failing one either stops the bootstrap or gets skipped depending on its `on_failure`.
+fn invoke_targets(mut invoke: impl FnMut(&Target) -> anyhow::Result<()>) -> anyhow::Result<()> {
+    for target in get_config().all_targets() {
+        let result = invoke(&target).with_context(|| format!("Failed to invoke target {} ({})", target.name, target.path.display()));
+
+        match target.on_failure {
+            OnFailure::Skip => {
+                if let Err(e) = result {
+                    warn!("{e:?}, skipping");
+                }
+            }
+            OnFailure::Fatal => result?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Instant};
+
+    use super::*;
+
+    #[test]
+    fn sync_and_async_entrypoints() {
+        static READY: Ready = Ready::new();
+        static SIGNALED: Ready = Ready::new();
+        static NEVER: Ready = Ready::new();
+
+        // Synchronous entrypoints run on the calling thread and their errors are passed on
+        let caller = thread::current().id();
+        spawn_entrypoints(false, Duration::ZERO, &READY, move || {
+            assert_eq!(thread::current().id(), caller);
+            Ok(())
+        })
+        .unwrap();
+        assert!(spawn_entrypoints(false, Duration::ZERO, &READY, || anyhow::bail!("failed")).is_err());
+
+        // Without a timeout the game doesn't wait at all
+        let (sender, receiver) = mpsc::channel::<()>();
+        spawn_entrypoints(true, Duration::ZERO, &READY, move || {
+            assert_ne!(thread::current().id(), caller);
+            _ = receiver.recv();
+            Ok(())
+        })
+        .unwrap();
+        drop(sender);
+
+        // Signaling ready lets the game continue before the entrypoint returns
+        let (sender, receiver) = mpsc::channel::<()>();
+        let start = Instant::now();
+        spawn_entrypoints(true, Duration::from_secs(30), &SIGNALED, move || {
+            SIGNALED.signal();
+            _ = receiver.recv();
+            Ok(())
+        })
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(30));
+        drop(sender);
+
+        // Otherwise it gives up after the timeout
+        let (sender, receiver) = mpsc::channel::<()>();
+        let start = Instant::now();
+        spawn_entrypoints(true, Duration::from_millis(50), &NEVER, move || {
+            _ = receiver.recv();
+            Ok(())
+        })
+        .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        drop(sender);
+        assert!(NEVER.wait(Duration::from_secs(30)), "returning signals ready");
+    }
+}
@@ -3,11 +3,12 @@ use std::{
     ffi::{CStr, CString, c_char, c_void},
     fs,
     io::ErrorKind,
+    mem,
     mem::DropGuard,
     path::{Path, PathBuf},
     str,
     sync::{
-        Once, OnceLock,
+        Mutex, Once, OnceLock,
         atomic::{AtomicBool, Ordering},
     },
     thread::sleep,
@@ -16,12 +17,19 @@ use std::{
 
 use anyhow::{Context, bail};
 use bitflags::bitflags;
+use doorstop_config::Target;
 use doorstop_shared::OsStrExt;
 use log::{info, trace, warn};
 
 use crate::{
-    fatal, get_config, hook_fn,
-    utils::bindings::{BindingsStruct, bindings},
+    api,
+    api::RuntimeKind,
+    fatal, get_config, hook_fn, runtimes,
+    runtimes::{get_entrypoint, get_stop_method, invoke_targets, run_entrypoints},
+    utils::{
+        arguments::strip_c_arguments,
+        bindings::{BindingsStruct, bindings},
+    },
 };
 
 unsafe extern "C" {
@@ -31,6 +39,10 @@ unsafe extern "C" {
     pub type MonoImage;
     pub type MonoMethod;
     pub type MonoObject;
+    pub type MonoClass;
+    pub type MonoArray;
+    pub type MonoString;
+    pub type MonoThread;
 }
 
 #[allow(non_camel_case_types)]
@@ -113,6 +125,15 @@ bindings! {
         // MonoDomain* mono_domain_get()
         mono_domain_get: unsafe extern "C" fn() -> *const MonoDomain,
 
+        // MonoDomain* mono_get_root_domain()
+        mono_get_root_domain: unsafe extern "C" fn() -> *const MonoDomain,
+
+        // MonoThread* mono_thread_attach(MonoDomain* domain)
+        mono_thread_attach: unsafe extern "C" fn(domain: *const MonoDomain) -> *const MonoThread,
+
+        // void mono_thread_detach(MonoThread* thread)
+        mono_thread_detach: unsafe extern "C" fn(thread: *const MonoThread),
+
         // MonoAssembly* mono_domain_assembly_open(MonoDomain* domain, const char* name)
         mono_domain_assembly_open: unsafe extern "C" fn(domain: *const MonoDomain, name: *const c_char) -> *const MonoAssembly,
 
@@ -128,6 +149,21 @@ bindings! {
         // void mono_method_desc_free(MonoMethodDesc* desc)
         mono_method_desc_free: unsafe extern "C" fn(desc: *const MonoMethodDesc),
 
+        // MonoClass* mono_get_string_class()
+        mono_get_string_class: unsafe extern "C" fn() -> *const MonoClass,
+
+        // MonoString* mono_string_new(MonoDomain* domain, const char* text)
+        mono_string_new: unsafe extern "C" fn(domain: *const MonoDomain, text: *const c_char) -> *const MonoString,
+
+        // MonoArray* mono_array_new(MonoDomain* domain, MonoClass* eclass, uintptr_t n)
+        mono_array_new: unsafe extern "C" fn(domain: *const MonoDomain, eclass: *const MonoClass, n: usize) -> *const MonoArray,
+
+        // char* mono_array_addr_with_size(MonoArray* array, int size, uintptr_t idx)
+        mono_array_addr_with_size: unsafe extern "C" fn(array: *const MonoArray, size: i32, idx: usize) -> *mut c_char,
+
+        // void mono_gc_wbarrier_set_arrayref(MonoArray* arr, void* slot_ptr, MonoObject* value)
+        mono_gc_wbarrier_set_arrayref: Option<unsafe extern "C" fn(arr: *const MonoArray, slot_ptr: *mut c_void, value: *const MonoObject)>,
+
         // MonoObject* mono_runtime_invoke(MonoMethod* method, void* obj, void** params, MonoObject** exc)
         mono_runtime_invoke: unsafe extern "C" fn(method: *const MonoMethod, obj: *mut c_void, params: *mut *mut c_void, exc: *mut *const MonoObject) -> *const MonoObject,
 
@@ -143,6 +179,43 @@ bindings! {
 }
 
 impl Mono {
+    unsafe fn new_string_array(&self, domain: *const MonoDomain, items: &[String]) -> anyhow::Result<*const MonoArray> {
+        unsafe {
+            let array = (self.mono_array_new)(domain, (self.mono_get_string_class)(), items.len());
+            assert!(!array.is_null());
+
+            for (i, item) in items.iter().enumerate() {
+                let item = CString::new(item.as_str())?;
+                let string = (self.mono_string_new)(domain, item.as_ptr());
+
+                let slot = (self.mono_array_addr_with_size)(array, i32::try_from(size_of::<*const MonoString>()).unwrap(), i);
+                if let Some(mono_gc_wbarrier_set_arrayref) = self.mono_gc_wbarrier_set_arrayref {
+                    mono_gc_wbarrier_set_arrayref(array, slot.cast(), string as *const MonoObject);
+                } else {
+                    slot.cast::<*const MonoString>().write_unaligned(string);
+                }
+            }
+
+            Ok(array)
+        }
+    }
+
+    unsafe fn exception_error(&self, exc: *const MonoObject, message: &str) -> anyhow::Error {
+        unsafe {
+            if let Some(mono_object_to_string) = self.mono_object_to_string
+                && let Some(mono_string_to_utf8) = self.mono_string_to_utf8
+            {
+                let string_object = mono_object_to_string(exc, std::ptr::null_mut());
+                let str = DropGuard::new(mono_string_to_utf8(string_object), |str| self.free(str));
+                let str = CStr::from_ptr(*str);
+                return anyhow::anyhow!("{message}: {}", str.display());
+            }
+
+            (self.mono_print_unhandled_exception)(exc);
+            anyhow::anyhow!("{message}")
+        }
+    }
+
     unsafe fn free(&self, ptr: *const c_char) {
         if let Some(free) = self.mono_unity_g_free.or(self.mono_free).or(self.g_free) {
             unsafe { free(ptr) };
@@ -158,6 +231,13 @@ static BOOTSTRAP_ONCE: Once = Once::new();
 
 static DURING_MONO_INIT: AtomicBool = AtomicBool::new(false);
 
+struct StopMethod(*const MonoMethod);
+
+// Only invoked once from whichever thread ends up calling runtimes::stop
+unsafe impl Send for StopMethod {}
+
+static STOP_METHODS: Mutex<Vec<StopMethod>> = Mutex::new(Vec::new());
+
 pub fn try_hook(module: *mut c_void, name: &str, address: *const c_void) -> Option<*const c_void> {
     if name.starts_with("mono_") {
         MONO.get_or_init(|| {
@@ -246,6 +326,22 @@ pub fn try_hook(module: *mut c_void, name: &str, address: *const c_void) -> Opti
             }
         ) as *const _),
 
+        // Called by Unity on quit, give the targets a chance to clean up while everything is still alive
+        "mono_jit_cleanup" => Some(hook_fn!(address, extern "C" fn(orig, domain: *const MonoDomain), {
+            trace!("mono_jit_cleanup");
+            runtimes::stop();
+            unsafe { orig(domain) };
+            runtimes::runtime_shut_down();
+        }) as *const _),
+
+        // Environment.GetCommandLineArgs
+        "mono_runtime_set_main_args" => Some(hook_fn!(address, extern "C" fn(orig, argc: i32, argv: *const *const c_char) -> i32, {
+            unsafe {
+                let (argc, argv) = strip_c_arguments(argc, argv);
+                orig(argc, argv)
+            }
+        }) as *const _),
+
         // Legacy mono's debugger-agent relied on profiler events, but production UnityPlayer resets them
         // Hook mono_profiler_set_events to make it cumulative
         "mono_profiler_set_events" => Some(hook_fn!(address, extern "C" fn(orig, events: MonoProfileFlags), {
@@ -325,6 +421,7 @@ fn init(mono: &Mono, is_net35: bool) {
         let root_dir = CStr::from_ptr((mono.mono_assembly_getrootdir)()).to_str().unwrap();
 
         env::set_var("DOORSTOP_MANAGED_FOLDER_DIR", root_dir);
+        api::set_runtime(RuntimeKind::Mono, Path::new(root_dir));
 
         if let Some(search_path_override) = get_config().mono_dll_search_path_override.as_ref() {
             let mut new_search_path = search_path_override.clone();
@@ -333,7 +430,7 @@ fn init(mono: &Mono, is_net35: bool) {
 
             env::set_var("DOORSTOP_DLL_SEARCH_DIRS", &new_search_path);
 
-            info!("Overriding search path to {new_search_path}");
+            info!(path:% = new_search_path; "Overriding search path to {new_search_path}");
             let new_search_path = CString::new(new_search_path).unwrap();
             (mono.mono_set_assemblies_path)(new_search_path.as_ptr());
         } else {
@@ -384,53 +481,118 @@ fn init(mono: &Mono, is_net35: bool) {
 }
 
 fn bootstrap() -> anyhow::Result<()> {
-    unsafe {
+    if get_config().all_targets().next().is_none() {
+        warn!("No target assembly specified, skipping bootstrap");
+        return Ok(());
+    }
+
+    run_entrypoints(|| unsafe {
         let mono = MONO.get().unwrap();
-        let config = get_config();
 
-        if let Some(target_assembly) = config.target_assembly.as_ref() {
-            let domain = (mono.mono_domain_get)();
-            assert!(!domain.is_null());
+        // The entrypoint thread is new to mono, and has to be unregistered again before it exits
+        let thread = get_config().entrypoint_async.then(|| (mono.mono_thread_attach)((mono.mono_get_root_domain)()));
 
-            let target_assembly_path = target_assembly.to_cstr().unwrap();
+        let domain = (mono.mono_domain_get)();
+        assert!(!domain.is_null());
 
-            let assembly = (mono.mono_domain_assembly_open)(domain, target_assembly_path.as_ptr());
-            if assembly.is_null() {
-                bail!("Failed to load target assembly");
-            }
+        let result = invoke_targets(|target| invoke_target(mono, domain, target));
 
-            let image = (mono.mono_assembly_get_image)(assembly);
-            assert!(!image.is_null());
+        if let Some(thread) = thread {
+            (mono.mono_thread_detach)(thread);
+        }
+
+        result
+    })
+}
 
-            let desc = (mono.mono_method_desc_new)(c"Doorstop.Entrypoint:Start".as_ptr(), 1);
+unsafe fn invoke_target(mono: &Mono, domain: *const MonoDomain, target: &Target) -> anyhow::Result<()> {
+    unsafe {
+        let config = get_config();
+
+        let target_assembly_path = target.path.to_cstr().unwrap();
+
+        let assembly = (mono.mono_domain_assembly_open)(domain, target_assembly_path.as_ptr());
+        if assembly.is_null() {
+            bail!("Failed to load target assembly");
+        }
+
+        let image = (mono.mono_assembly_get_image)(assembly);
+        assert!(!image.is_null());
+
+        let find_method = |name: &str| -> anyhow::Result<*const MonoMethod> {
+            let name = CString::new(name)?;
+            let desc = (mono.mono_method_desc_new)(name.as_ptr(), 1);
             let desc = DropGuard::new(desc, |desc| (mono.mono_method_desc_free)(desc));
             assert!(!desc.is_null());
 
-            let method = (mono.mono_method_desc_search_in_image)(*desc, image);
+            Ok((mono.mono_method_desc_search_in_image)(*desc, image))
+        };
+
+        let (type_name, method_name) = get_entrypoint(target)?;
+
+        // Prefer the overload accepting entrypoint_args
+        let mut params: Vec<*mut c_void> = Vec::new();
+        let mut method = find_method(&format!("{type_name}:{method_name}(string[])"))?;
+        if method.is_null() {
+            method = find_method(&format!("{type_name}:{method_name}"))?;
+            if !config.entrypoint_args.is_empty() {
+                warn!("Entrypoint method doesn't accept a string[], entrypoint_args will be ignored");
+            }
+        } else {
+            params.push((mono.new_string_array(domain, &config.entrypoint_args)?).cast_mut().cast());
+        }
+
+        if method.is_null() {
+            bail!("Failed to find entrypoint method {type_name}:{method_name} in target assembly");
+        }
+
+        let params_ptr = if params.is_empty() { std::ptr::null_mut() } else { params.as_mut_ptr() };
+
+        let mut exc: *const MonoObject = std::ptr::null();
+        (mono.mono_runtime_invoke)(method, std::ptr::null_mut(), params_ptr, &raw mut exc);
+
+        if !exc.is_null() {
+            return Err(mono.exception_error(exc, "Failed to invoke entrypoint method"));
+        }
+
+        if let Some((type_name, method_name)) = get_stop_method(target)? {
+            let method = find_method(&format!("{type_name}:{method_name}"))?;
             if method.is_null() {
-                bail!("Failed to find entrypoint method in target assembly");
+                warn!("Failed to find stop method {type_name}:{method_name} in target assembly");
+            } else {
+                STOP_METHODS.lock().unwrap().push(StopMethod(method));
             }
+        }
+
+        Ok(())
+    }
+}
+
+pub(super) fn stop() {
+    let methods = mem::take(&mut *STOP_METHODS.lock().unwrap());
+    if methods.is_empty() {
+        return;
+    }
+
+    unsafe {
+        let mono = MONO.get().unwrap();
+
+        let domain = (mono.mono_get_root_domain)();
+        if domain.is_null() {
+            warn!("Mono is already shut down, skipping stop methods");
+            return;
+        }
 
+        // exit can be called from a thread mono has never seen
+        (mono.mono_thread_attach)(domain);
+
+        for StopMethod(method) in methods {
             let mut exc: *const MonoObject = std::ptr::null();
             (mono.mono_runtime_invoke)(method, std::ptr::null_mut(), std::ptr::null_mut(), &raw mut exc);
 
             if !exc.is_null() {
-                if let Some(mono_object_to_string) = mono.mono_object_to_string
-                    && let Some(mono_string_to_utf8) = mono.mono_string_to_utf8
-                {
-                    let string_object = mono_object_to_string(exc, std::ptr::null_mut());
-                    let str = DropGuard::new(mono_string_to_utf8(string_object), |str| mono.free(str));
-                    let str = CStr::from_ptr(*str);
-                    bail!("Failed to invoke entrypoint method: {}", str.display());
-                }
-
-                (mono.mono_print_unhandled_exception)(exc);
-                bail!("Failed to invoke entrypoint method");
+                warn!("{}", mono.exception_error(exc, "Failed to invoke stop method"));
             }
-        } else {
-            warn!("No target assembly specified, skipping bootstrap");
         }
-
-        Ok(())
     }
 }
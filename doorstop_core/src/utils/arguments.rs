@@ -0,0 +1,211 @@
+use std::{
+    ffi::{CStr, CString, c_char},
+    ptr, slice,
+};
+
+use crate::{add_extra_arguments, strip_arguments};
+
+#[cfg(windows)]
+const QUOTE: u16 = b'"' as u16;
+#[cfg(windows)]
+const BACKSLASH: u16 = b'\\' as u16;
+
+/// Returns `argv` changed by `map`, the new array is leaked since the callee is free to keep it around.
+unsafe fn map_raw_arguments<T>(argc: i32, argv: *const *const T, map: impl FnOnce(Vec<*const T>) -> Vec<*const T>) -> (i32, *const *const T) {
+    if argv.is_null() || argc <= 0 {
+        return (argc, argv);
+    }
+
+    let args = unsafe { slice::from_raw_parts(argv, usize::try_from(argc).unwrap()) };
+    let mut new_args = map(args.to_vec());
+
+    if new_args == args {
+        return (argc, argv);
+    }
+
+    let new_argc = i32::try_from(new_args.len()).unwrap();
+    new_args.push(ptr::null());
+
+    (new_argc, Box::leak(new_args.into_boxed_slice()).as_ptr())
+}
+
+/// Returns `argv` without the `--doorstop-*` arguments.
+pub unsafe fn strip_raw_arguments<T>(argc: i32, argv: *const *const T, to_string: impl Fn(*const T) -> String) -> (i32, *const *const T) {
+    unsafe { map_raw_arguments(argc, argv, |args| strip_arguments(args, |arg| to_string(*arg))) }
+}
+
+#[must_use]
+pub unsafe fn strip_c_arguments(argc: i32, argv: *const *const c_char) -> (i32, *const *const c_char) {
+    unsafe { strip_raw_arguments(argc, argv, |arg| CStr::from_ptr(arg).to_string_lossy().into_owned()) }
+}
+
+/// Returns `argv` without the `--doorstop-*` arguments and with the extra arguments from the config added.
+/// Only meant for the arguments Unity starts with, the ones it passes on already include the extra arguments.
+#[must_use]
+pub unsafe fn rewrite_c_arguments(argc: i32, argv: *const *const c_char) -> (i32, *const *const c_char) {
+    unsafe {
+        map_raw_arguments(argc, argv, |args| {
+            let args = strip_arguments(args, |arg| CStr::from_ptr(*arg).to_string_lossy().into_owned());
+            add_extra_arguments(args, |arg| CString::new(arg).unwrap_or_default().into_raw().cast_const())
+        })
+    }
+}
+
+pub unsafe fn strip_utf16_arguments(argc: i32, argv: *const *const u16) -> (i32, *const *const u16) {
+    unsafe {
+        strip_raw_arguments(argc, argv, |arg| {
+            let mut len = 0;
+            while *arg.add(len) != 0 {
+                len += 1;
+            }
+
+            String::from_utf16_lossy(slice::from_raw_parts(arg, len))
+        })
+    }
+}
+
+/// Splits a command line into the raw arguments (quotes included) and their values, following the same rules as `CommandLineToArgvW`.
+#[cfg(windows)]
+fn split_command_line(command_line: &[u16]) -> Vec<(&[u16], String)> {
+    let is_whitespace = |c: u16| c == u16::from(b' ') || c == u16::from(b'\t');
+
+    let command_line = command_line.split(|c| *c == 0).next().unwrap_or_default();
+
+    let mut result = Vec::new();
+    let mut i = 0;
+
+    while i < command_line.len() {
+        if is_whitespace(command_line[i]) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut value = Vec::new();
+        let mut in_quotes = false;
+
+        while i < command_line.len() && (in_quotes || !is_whitespace(command_line[i])) {
+            match command_line[i] {
+                BACKSLASH => {
+                    let backslashes = command_line[i..].iter().take_while(|c| **c == BACKSLASH).count();
+                    i += backslashes;
+
+                    if command_line.get(i) == Some(&QUOTE) {
+                        // 2n backslashes followed by a quote are n backslashes, 2n + 1 are n backslashes and a literal quote
+                        value.extend(std::iter::repeat_n(BACKSLASH, backslashes / 2));
+                        if backslashes % 2 == 1 {
+                            value.push(QUOTE);
+                            i += 1;
+                        }
+                    } else {
+                        value.extend(std::iter::repeat_n(BACKSLASH, backslashes));
+                    }
+                }
+                QUOTE => {
+                    in_quotes = !in_quotes;
+                    i += 1;
+                }
+                c => {
+                    value.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        result.push((&command_line[start..i], String::from_utf16_lossy(&value)));
+    }
+
+    result
+}
+
+/// Quotes an argument so that `CommandLineToArgvW` splits it back into the same value.
+#[cfg(windows)]
+fn quote_argument(arg: &str) -> Vec<u16> {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.encode_utf16().collect();
+    }
+
+    let mut result = vec![QUOTE];
+    let mut backslashes = 0;
+
+    for c in arg.encode_utf16() {
+        if c == BACKSLASH {
+            backslashes += 1;
+            continue;
+        }
+
+        // Backslashes only need escaping when followed by a quote
+        let escaped_backslashes = if c == QUOTE { backslashes * 2 + 1 } else { backslashes };
+        result.extend(std::iter::repeat_n(BACKSLASH, escaped_backslashes));
+        result.push(c);
+        backslashes = 0;
+    }
+
+    // The closing quote follows them too
+    result.extend(std::iter::repeat_n(BACKSLASH, backslashes * 2));
+    result.push(QUOTE);
+    result
+}
+
+/// Returns the nul-terminated command line without the `--doorstop-*` arguments and with the extra arguments from the config added.
+/// The original arguments keep their quoting.
+#[cfg(windows)]
+pub fn rewrite_command_line(command_line: &[u16], skip_program_name: bool) -> Vec<u16> {
+    let args = split_command_line(command_line).into_iter().map(|(raw, value)| (raw.to_vec(), value)).collect();
+    let args = strip_arguments(args, |(_, value)| value.clone());
+    let args = add_extra_arguments(args, |arg| (quote_argument(arg), arg.to_string()));
+
+    let mut result = Vec::new();
+    for (raw, _) in args.iter().skip(usize::from(skip_program_name)) {
+        if !result.is_empty() {
+            result.push(u16::from(b' '));
+        }
+
+        result.extend_from_slice(raw);
+    }
+
+    result.push(0);
+    result
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    #[allow(clippy::needless_raw_string_hashes)]
+    fn command_line_splitting() {
+        fn test(input: &str, expected: &[(&str, &str)]) {
+            let input = utf16(input);
+            let actual: Vec<(String, String)> = split_command_line(&input)
+                .into_iter()
+                .map(|(raw, value)| (String::from_utf16_lossy(raw), value))
+                .collect();
+            let expected: Vec<(String, String)> = expected.iter().map(|(raw, value)| ((*raw).to_string(), (*value).to_string())).collect();
+            assert_eq!(actual, expected);
+        }
+
+        test(r#"game.exe"#, &[(r#"game.exe"#, r#"game.exe"#)]);
+        test(
+            r#""C:\Program Files\game.exe"  arg"#,
+            &[(r#""C:\Program Files\game.exe""#, r#"C:\Program Files\game.exe"#), ("arg", "arg")],
+        );
+        test(r#"a "b c" d"#, &[("a", "a"), (r#""b c""#, "b c"), ("d", "d")]);
+        test(r#"a\\"b c" \"d"#, &[(r#"a\\"b c""#, r#"a\b c"#), (r#"\"d"#, r#""d"#)]);
+        test(r#"a\\b"#, &[(r#"a\\b"#, r#"a\\b"#)]);
+    }
+
+    #[test]
+    fn quoted_arguments_split_back() {
+        for arg in ["-force-vulkan", "", "a b", r"C:\Program Files\", r#"say "hi""#, r#"a\"b"#, r"a\\b c"] {
+            let quoted = quote_argument(arg);
+            let split = split_command_line(&quoted);
+            assert_eq!(split.len(), 1, "{arg} was split into {split:?}");
+            assert_eq!(split[0].1, arg);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    io::{self, Write},
+    mem,
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, Once, TryLockError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use dtor::dtor;
+
+/// How many writes can be queued before logging blocks until the background thread catches up.
+const CAPACITY: usize = 4096;
+
+/// How long [`flush_logs`] waits for a write in progress, the background thread might have been killed in the middle of one on exit.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+static WRITERS: Mutex<Vec<Arc<Shared>>> = Mutex::new(Vec::new());
+static CRASH_HANDLER: Once = Once::new();
+
+/// What an [`AsyncWriter`] writes to.
+pub(crate) trait Output: Write + Send {
+    /// Whether writing won't have to open the file first, crashes are only flushed to outputs that are already open.
+    fn is_open(&self) -> bool;
+}
+
+/// Hands writes off to a background thread, so logging from hooks on the game's threads doesn't wait on the disk.
+/// Everything queued since the last batch is written at once, [`flush_logs`] writes the rest synchronously.
+pub(crate) struct AsyncWriter {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    queue: Mutex<Vec<Vec<u8>>>,
+    changed: Condvar,
+    output: Mutex<Box<dyn Output>>,
+}
+
+impl AsyncWriter {
+    pub(crate) fn new(output: impl Output + 'static) -> io::Result<Self> {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Vec::new()),
+            changed: Condvar::new(),
+            output: Mutex::new(Box::new(output)),
+        });
+
+        let worker = Arc::clone(&shared);
+        thread::Builder::new().name("doorstop-log-writer".to_string()).spawn(move || worker.run())?;
+
+        WRITERS.lock().unwrap().push(Arc::clone(&shared));
+        CRASH_HANDLER.call_once(install_crash_handler);
+
+        Ok(Self { shared })
+    }
+}
+
+impl Shared {
+    fn run(&self) {
+        loop {
+            drop(self.changed.wait_while(self.queue.lock().unwrap(), |queue| queue.is_empty()).unwrap());
+
+            let mut output = self.output.lock().unwrap();
+            _ = self.write_queued(&mut output);
+        }
+    }
+
+    /// The output has to be locked before taking from the queue, so batches are written in order.
+    fn write_queued(&self, output: &mut Box<dyn Output>) -> io::Result<()> {
+        let batch = mem::take(&mut *self.queue.lock().unwrap());
+        self.changed.notify_all();
+
+        for buf in batch {
+            output.write_all(&buf)?;
+        }
+
+        output.flush()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+
+        loop {
+            match self.output.try_lock() {
+                Ok(mut output) => return self.write_queued(&mut output),
+                Err(TryLockError::Poisoned(e)) => return self.write_queued(&mut e.into_inner()),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => thread::sleep(Duration::from_millis(1)),
+                Err(TryLockError::WouldBlock) => return Err(io::ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+
+    /// Same as [`Shared::flush`], but safe to call from a crash handler.
+    /// The crashing thread might be holding either lock or be in the middle of opening the file, so this gives up instead of waiting and only writes to an already open output.
+    /// The batch is leaked rather than freed, the heap might be what's broken.
+    fn flush_on_crash(&self) -> io::Result<()> {
+        let Some(mut output) = try_lock(&self.output).filter(|output| output.is_open()) else {
+            return Err(io::ErrorKind::WouldBlock.into());
+        };
+        let Some(mut queue) = try_lock(&self.queue) else {
+            return Err(io::ErrorKind::WouldBlock.into());
+        };
+
+        let batch = mem::take(&mut *queue);
+        drop(queue);
+
+        for buf in &batch {
+            output.write_all(buf)?;
+        }
+        mem::forget(batch);
+
+        output.flush()
+    }
+}
+
+fn try_lock<T: ?Sized>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+impl Write for AsyncWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut queue = self
+            .shared
+            .changed
+            .wait_while(self.shared.queue.lock().unwrap(), |queue| queue.len() >= CAPACITY)
+            .unwrap();
+        queue.push(buf.to_vec());
+        drop(queue);
+
+        self.shared.changed.notify_all();
+        Ok(buf.len())
+    }
+
+    /// fern flushes after every record, actually flushing is left to the background thread and [`flush_logs`].
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Synchronously writes everything still queued by every [`AsyncWriter`], used on exit, in [`crate::fatal`] and on panics.
+pub(crate) fn flush_logs() {
+    let Ok(writers) = WRITERS.try_lock() else {
+        return;
+    };
+
+    for writer in writers.iter() {
+        _ = writer.flush();
+    }
+}
+
+#[dtor]
+unsafe fn flush_logs_on_exit() {
+    flush_logs();
+}
+
+/// Flushes once when the process crashes, a crash inside of the flush itself shouldn't try again.
+/// Only writes to log files that are already open and skips any writer whose locks are taken, see [`Shared::flush_on_crash`].
+fn flush_logs_on_crash() {
+    static CRASHED: AtomicBool = AtomicBool::new(false);
+
+    if CRASHED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    let Some(writers) = try_lock(&WRITERS) else {
+        return;
+    };
+
+    for writer in writers.iter() {
+        _ = writer.flush_on_crash();
+    }
+}
+
+/// Flushes the logs on native crashes, then hands the crash to whoever handled it before.
+/// This is best effort, Mono and Unity install their own crash handlers later and don't always pass crashes on.
+#[cfg(unix)]
+fn install_crash_handler() {
+    use std::{
+        ffi::{c_int, c_void},
+        mem::MaybeUninit,
+        ptr,
+        sync::OnceLock,
+    };
+
+    use libc::{SA_ONSTACK, SA_SIGINFO, SIG_DFL, SIG_IGN, SIGABRT, SIGBUS, SIGFPE, SIGILL, SIGSEGV, sigaction, siginfo_t};
+
+    const SIGNALS: [c_int; 5] = [SIGSEGV, SIGBUS, SIGILL, SIGFPE, SIGABRT];
+
+    static PREVIOUS: OnceLock<Vec<(c_int, sigaction)>> = OnceLock::new();
+
+    extern "C" fn handle_crash(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+        flush_logs_on_crash();
+
+        let previous = PREVIOUS
+            .get()
+            .and_then(|previous| previous.iter().find(|(previous_signal, _)| *previous_signal == signal));
+
+        unsafe {
+            match previous {
+                Some((_, action)) if action.sa_sigaction != SIG_DFL && action.sa_sigaction != SIG_IGN => {
+                    if action.sa_flags & SA_SIGINFO == 0 {
+                        let handler: extern "C" fn(c_int) = mem::transmute(action.sa_sigaction);
+                        handler(signal);
+                    } else {
+                        let handler: extern "C" fn(c_int, *mut siginfo_t, *mut c_void) = mem::transmute(action.sa_sigaction);
+                        handler(signal, info, context);
+                    }
+                }
+                // The default action takes the process down as soon as the handler returns
+                _ => {
+                    libc::signal(signal, SIG_DFL);
+                    libc::raise(signal);
+                }
+            }
+        }
+    }
+
+    unsafe {
+        let previous = SIGNALS
+            .iter()
+            .map(|signal| {
+                let mut action = MaybeUninit::<sigaction>::zeroed();
+                libc::sigaction(*signal, ptr::null(), action.as_mut_ptr());
+                (*signal, action.assume_init())
+            })
+            .collect();
+        _ = PREVIOUS.set(previous);
+
+        let mut action: sigaction = mem::zeroed();
+        action.sa_sigaction = handle_crash as *const () as usize;
+        action.sa_flags = SA_SIGINFO | SA_ONSTACK;
+        libc::sigemptyset(&raw mut action.sa_mask);
+
+        for signal in SIGNALS {
+            libc::sigaction(signal, &raw const action, ptr::null_mut());
+        }
+    }
+}
+
+/// Flushes the logs on unhandled exceptions, then hands them to the previous filter.
+/// This is best effort, Unity's crash handler replaces the filter later and doesn't always pass exceptions on.
+#[cfg(windows)]
+fn install_crash_handler() {
+    use std::sync::OnceLock;
+
+    use windows::Win32::System::Diagnostics::Debug::{
+        EXCEPTION_CONTINUE_SEARCH, EXCEPTION_POINTERS, LPTOP_LEVEL_EXCEPTION_FILTER, SetUnhandledExceptionFilter,
+    };
+
+    static PREVIOUS: OnceLock<LPTOP_LEVEL_EXCEPTION_FILTER> = OnceLock::new();
+
+    unsafe extern "system" fn filter(info: *const EXCEPTION_POINTERS) -> i32 {
+        flush_logs_on_crash();
+
+        match PREVIOUS.get().copied().flatten() {
+            Some(previous) => unsafe { previous(info) },
+            None => EXCEPTION_CONTINUE_SEARCH,
+        }
+    }
+
+    _ = PREVIOUS.set(unsafe { SetUnhandledExceptionFilter(Some(filter)) });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output for SharedBuffer {
+        fn is_open(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn writes_everything_in_order() {
+        let buffer = SharedBuffer::default();
+        let mut writer = AsyncWriter::new(buffer.clone()).unwrap();
+
+        let mut expected = String::new();
+        for i in 0..CAPACITY * 2 {
+            let line = format!("line {i}\n");
+            writer.write_all(line.as_bytes()).unwrap();
+            expected.push_str(&line);
+        }
+
+        writer.shared.flush().unwrap();
+        assert_eq!(String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(), expected);
+
+        // A crash on a thread holding the queue lock gives up instead of waiting on it
+        let queue = writer.shared.queue.lock().unwrap();
+        assert!(writer.shared.flush_on_crash().is_err());
+        drop(queue);
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    fmt::{self, Write},
+    process, thread,
+    time::SystemTime,
+};
+
+use log::{
+    Record,
+    kv::{Error, Key, Value, VisitSource},
+};
+
+use crate::utils::time::UtcTime;
+
+/// Formats a record as a single line JSON object, structured fields passed to the `log` macros go into `fields`.
+pub(crate) fn format_record(message: &fmt::Arguments, record: &Record) -> String {
+    let mut line = String::new();
+
+    line.push_str("{\"time\":");
+    push_string(&mut line, &UtcTime::from(SystemTime::now()).to_string());
+    line.push_str(",\"level\":");
+    push_string(&mut line, record.level().as_str());
+    line.push_str(",\"target\":");
+    push_string(&mut line, record.target());
+    _ = write!(line, ",\"pid\":{},\"thread\":{}", process::id(), thread::current().id().as_u64());
+    line.push_str(",\"message\":");
+    push_string(&mut line, &message.to_string());
+
+    let mut fields = FieldsVisitor { line: &mut line, first: true };
+    _ = record.key_values().visit(&mut fields);
+    if !fields.first {
+        line.push('}');
+    }
+
+    line.push('}');
+    line
+}
+
+struct FieldsVisitor<'a> {
+    line: &'a mut String,
+    first: bool,
+}
+
+impl<'kvs> VisitSource<'kvs> for FieldsVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        self.line.push_str(if self.first { ",\"fields\":{" } else { "," });
+        self.first = false;
+
+        push_string(self.line, key.as_str());
+        self.line.push(':');
+        push_string(self.line, &value.to_string());
+
+        Ok(())
+    }
+}
+
+/// Appends `text` as a quoted JSON string.
+fn push_string(line: &mut String, text: &str) {
+    line.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c.is_control() => _ = write!(line, "\\u{:04x}", u32::from(c)),
+            c => line.push(c),
+        }
+    }
+
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn record_is_one_json_line() {
+        let fields = [("symbol", "mono_jit_init_version")];
+        let line = format_record(
+            &format_args!("first line\n\t\"second\" line\u{1}"),
+            &Record::builder()
+                .level(Level::Error)
+                .target("doorstop_core::patches")
+                .key_values(&fields)
+                .build(),
+        );
+
+        let expected = "\"level\":\"ERROR\",\"target\":\"doorstop_core::patches\",";
+        assert!(line.starts_with("{\"time\":\""), "{line}");
+        assert!(line.contains(expected), "{line}");
+        assert!(
+            line.ends_with(r#""message":"first line\n\t\"second\" line\u0001","fields":{"symbol":"mono_jit_init_version"}}"#),
+            "{line}"
+        );
+        assert!(!line.contains('\n'));
+    }
+}
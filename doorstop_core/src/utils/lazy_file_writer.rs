@@ -1,23 +1,90 @@
 use std::{
+    ffi::OsString,
     fs,
     fs::{File, OpenOptions, TryLockError},
-    io::{self, Write},
-    path::PathBuf,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
     sync::Mutex,
 };
 
+use crate::utils::{
+    async_writer::Output,
+    log_files::{rotate_log, rotated_log_path},
+};
+
 pub(crate) struct LazyFileWriter {
     path: PathBuf,
-    file: Mutex<Option<File>>,
+    /// How many logs to keep, including the current one, previous ones are moved to `<path>.1`, `<path>.2` and so on.
+    keep: usize,
+    /// Anything past this many bytes is dropped, `0` for no limit.
+    max_size: u64,
+    file: Mutex<Option<LogFile>>,
+}
+
+struct LogFile {
+    /// Flushed after every batch by [`AsyncWriter`](crate::utils::async_writer::AsyncWriter).
+    file: BufWriter<File>,
+    written: u64,
 }
 
 impl LazyFileWriter {
-    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+    pub(crate) fn new(path: impl Into<PathBuf>, keep: usize, max_size: u64) -> Self {
         Self {
             path: path.into(),
+            keep,
+            max_size,
             file: Mutex::new(None),
         }
     }
+
+    fn open(&self) -> io::Result<LogFile> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut attempt = 0;
+
+        loop {
+            let path = if attempt == 0 { &self.path } else { &instance_path(&self.path, attempt) };
+
+            let mut file = OpenOptions::new().create(true).read(true).write(true).truncate(false).open(path)?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    attempt += 1;
+                    continue;
+                }
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+
+            // The file is locked, so it's copied through this handle instead of being renamed
+            if attempt == 0 && self.keep > 1 && file.metadata()?.len() > 0 {
+                rotate_log(path, 1, self.keep)?;
+                file.seek(SeekFrom::Start(0))?;
+                io::copy(&mut file, &mut File::create(rotated_log_path(path, 1))?)?;
+                file.seek(SeekFrom::Start(0))?;
+            }
+
+            file.set_len(0)?;
+
+            return Ok(LogFile {
+                file: BufWriter::new(file),
+                written: 0,
+            });
+        }
+    }
+}
+
+/// Used when another instance holds the lock on `path`, named so it doesn't clash with rotated logs, e.g. `doorstop-1.log`.
+fn instance_path(path: &Path, attempt: usize) -> PathBuf {
+    let mut file_name = OsString::from(path.file_stem().unwrap_or_default());
+    file_name.push(format!("-{attempt}"));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+
+    path.with_file_name(file_name)
 }
 
 impl Write for LazyFileWriter {
@@ -25,43 +92,68 @@ impl Write for LazyFileWriter {
         let mut file_guard = self.file.lock().unwrap();
 
         if file_guard.is_none() {
-            if let Some(parent) = self.path.parent() {
-                fs::create_dir_all(parent)?;
-            }
+            *file_guard = Some(self.open()?);
+        }
 
-            let mut attempt = 0;
-
-            loop {
-                let path = if attempt == 0 {
-                    &self.path
-                } else {
-                    &self.path.with_added_extension(attempt.to_string())
-                };
-
-                let file = OpenOptions::new().create(true).write(true).truncate(false).open(path)?;
-                match file.try_lock() {
-                    Ok(()) => {}
-                    Err(TryLockError::WouldBlock) => {
-                        attempt += 1;
-                        continue;
-                    }
-                    Err(TryLockError::Error(e)) => return Err(e),
-                }
-                file.set_len(0)?;
+        let log_file = file_guard.as_mut().unwrap();
+        let length = buf.len() as u64;
 
-                *file_guard = Some(file);
-                break;
+        if self.max_size > 0 && log_file.written.saturating_add(length) > self.max_size {
+            if log_file.written <= self.max_size {
+                writeln!(log_file.file, "[doorstop] Log file reached {} bytes, dropping the rest", self.max_size)?;
+                log_file.written = u64::MAX;
             }
+
+            return Ok(buf.len());
         }
 
-        file_guard.as_mut().unwrap().write(buf)
+        log_file.file.write_all(buf)?;
+        log_file.written += length;
+
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> io::Result<()> {
         let mut file_guard = self.file.lock().unwrap();
         match file_guard.as_mut() {
-            Some(file) => file.flush(),
+            Some(log_file) => log_file.file.flush(),
             None => Ok(()),
         }
     }
 }
+
+impl Output for LazyFileWriter {
+    fn is_open(&self) -> bool {
+        self.file.try_lock().is_ok_and(|file| file.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn rotation_and_size_cap() {
+        let dir = env::temp_dir().join(format!("doorstop-lazy-file-writer-{}", std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+        let path = dir.join("doorstop.log");
+
+        for session in 0..4 {
+            let mut writer = LazyFileWriter::new(&path, 3, 16);
+            writer.write_all(format!("session {session}\n").as_bytes()).unwrap();
+            writer.write_all(b"over the size cap\n").unwrap();
+        }
+
+        let read = |index| fs::read_to_string(rotated_log_path(&path, index)).unwrap();
+        assert_eq!(read(0), "session 3\n[doorstop] Log file reached 16 bytes, dropping the rest\n");
+        assert!(read(1).starts_with("session 2\n"));
+        assert!(read(2).starts_with("session 1\n"));
+        assert!(!rotated_log_path(&path, 3).exists());
+
+        assert_eq!(instance_path(&path, 2), dir.join("doorstop-2.log"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use doorstop_config::glob_matches;
+
+use crate::utils::time::{UtcTime, session_start};
+
+/// Placeholders that differ between sessions, old logs are found by replacing them with wildcards.
+const SESSION_PLACEHOLDERS: &[&str] = &["{pid}", "{timestamp}"];
+
+/// Replaces `{pid}`, `{timestamp}` and `{exe}` in a log path.
+pub(crate) fn expand_log_path(template: &Path, executable_name: &str) -> PathBuf {
+    let timestamp = UtcTime::from(session_start()).to_file_name();
+
+    PathBuf::from(
+        template
+            .to_string_lossy()
+            .replace("{pid}", &process::id().to_string())
+            .replace("{timestamp}", &timestamp)
+            .replace("{exe}", executable_name),
+    )
+}
+
+/// Whether old logs made from `template` have different names, see [`prune_logs`].
+pub(crate) fn has_session_placeholders(template: &Path) -> bool {
+    template
+        .file_name()
+        .is_some_and(|file_name| SESSION_PLACEHOLDERS.iter().any(|placeholder| file_name.to_string_lossy().contains(placeholder)))
+}
+
+/// Makes room for a new log, so that at most `keep` logs are left once it's created, `0` keeps all of them.
+/// If the file name has `{pid}` or `{timestamp}` in it, the oldest matching files are deleted, otherwise the log is rotated with [`rotate_log`].
+pub(crate) fn prune_logs(template: &Path, executable_name: &str, keep: usize) -> io::Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    let template = PathBuf::from(template.to_string_lossy().replace("{exe}", executable_name));
+    let (Some(dir), Some(file_name)) = (template.parent(), template.file_name()) else {
+        return Ok(());
+    };
+
+    if !has_session_placeholders(&template) {
+        return rotate_log(&template, 0, keep);
+    }
+
+    let pattern = SESSION_PLACEHOLDERS
+        .iter()
+        .fold(file_name.to_string_lossy().into_owned(), |pattern, placeholder| {
+            pattern.replace(placeholder, "*")
+        });
+
+    // Nothing to clean up if the directory doesn't exist yet
+    let Ok(entries) = fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir }) else {
+        return Ok(());
+    };
+
+    let mut logs: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| glob_matches(&pattern, &entry.file_name().to_string_lossy()))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+
+    logs.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    for (_, path) in logs.into_iter().skip(keep - 1) {
+        // Might still be open in another instance
+        _ = fs::remove_file(path);
+    }
+
+    Ok(())
+}
+
+/// `<path>.<index>`, or `path` itself for index `0`.
+pub(crate) fn rotated_log_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        path.to_path_buf()
+    } else {
+        path.with_added_extension(index.to_string())
+    }
+}
+
+/// Shifts `<path>.<first>` to `<path>.<first + 1>` and so on, overwriting `<path>.<keep - 1>`, where index `0` is `path` itself.
+pub(crate) fn rotate_log(path: &Path, first: usize, keep: usize) -> io::Result<()> {
+    for index in (first..keep.saturating_sub(1)).rev() {
+        let from = rotated_log_path(path, index);
+        if from.exists() {
+            fs::rename(&from, rotated_log_path(path, index + 1))?;
+        }
+    }
+
+    Ok(())
+}
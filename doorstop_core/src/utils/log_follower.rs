@@ -0,0 +1,39 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Copies everything written to the file at `path` to stderr, for as long as the process runs.
+/// The file is reopened on every poll, so it's never kept open while Unity wants exclusive access to it.
+pub(crate) fn follow_to_stderr(path: PathBuf) -> io::Result<()> {
+    thread::Builder::new().name("doorstop-log-follower".to_string()).spawn(move || {
+        let mut position = 0;
+        let mut buffer = Vec::new();
+
+        loop {
+            if let Ok(mut file) = File::open(&path) {
+                // Truncated, most likely reopened by Unity
+                if file.metadata().is_ok_and(|metadata| metadata.len() < position) {
+                    position = 0;
+                }
+
+                buffer.clear();
+                if file.seek(SeekFrom::Start(position)).is_ok()
+                    && let Ok(read) = file.read_to_end(&mut buffer)
+                {
+                    position += u64::try_from(read).unwrap();
+                    _ = io::stderr().write_all(&buffer);
+                }
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    })?;
+
+    Ok(())
+}
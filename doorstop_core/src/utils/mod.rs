@@ -1,4 +1,10 @@
+pub mod arguments;
+pub mod async_writer;
 pub mod bindings;
 pub mod hook;
+pub mod json_log;
 pub mod lazy_file_writer;
+pub mod log_files;
+pub mod log_follower;
 pub mod process_lock;
+pub mod time;
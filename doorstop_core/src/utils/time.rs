@@ -0,0 +1,95 @@
+use std::{
+    fmt,
+    sync::LazyLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Broken down UTC time, there's no need for time zones in log names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct UtcTime {
+    pub year: u64,
+    pub month: u64,
+    pub day: u64,
+    pub hour: u64,
+    pub minute: u64,
+    pub second: u64,
+    pub millisecond: u64,
+}
+
+impl From<SystemTime> for UtcTime {
+    // Based on http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn from(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let seconds = since_epoch.as_secs();
+        let days = seconds / 86400;
+
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let day_of_era = z % 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+
+        Self {
+            year: year_of_era + era * 400 + u64::from(month <= 2),
+            month,
+            day: day_of_year - (153 * shifted_month + 2) / 5 + 1,
+            hour: seconds % 86400 / 3600,
+            minute: seconds % 3600 / 60,
+            second: seconds % 60,
+            millisecond: u64::from(since_epoch.subsec_millis()),
+        }
+    }
+}
+
+impl UtcTime {
+    /// `YYYYMMDD-HHMMSS`, safe to use in file names.
+    pub(crate) fn to_file_name(self) -> String {
+        format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// `YYYY-MM-DDTHH:MM:SS.mmmZ`, used in log lines.
+impl fmt::Display for UtcTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millisecond
+        )
+    }
+}
+
+static SESSION_START: LazyLock<SystemTime> = LazyLock::new(SystemTime::now);
+
+/// When doorstop started, so every file created in the same session gets the same timestamp.
+pub(crate) fn session_start() -> SystemTime {
+    *SESSION_START
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn utc_time_formatting() {
+        fn test(seconds: u64, expected: &str) {
+            let time = UtcTime::from(UNIX_EPOCH + Duration::from_secs(seconds));
+            assert_eq!(time.to_file_name(), expected);
+        }
+
+        test(0, "19700101-000000");
+        test(951_782_400, "20000229-000000");
+        test(1_792_324_799, "20261018-115959");
+        test(4_107_542_400, "21000301-000000");
+
+        let time = UtcTime::from(UNIX_EPOCH + Duration::from_millis(1_792_324_799_042));
+        assert_eq!(time.to_string(), "2026-10-18T11:59:59.042Z");
+    }
+}
@@ -10,6 +10,8 @@ use std::{
 
 use anyhow::{anyhow, bail};
 
+mod options;
+
 #[cfg(windows)]
 mod windows;
 
@@ -0,0 +1,107 @@
+use std::{
+    env, fs,
+    path::{self, Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use doorstop_config::{Config, LoadContext, SETTINGS, Source};
+
+/// Options given before the command, `--<name> <value>` for every setting that has a `--doorstop-<name>` argument, and:
+/// - `--config <path>` and `--profile <name>`, passed on as `DOORSTOP_CONFIG` and `DOORSTOP_PROFILE`
+/// - `--write-config <path>`, writes the settings to a config file instead of launching the command
+///
+/// `--` ends the options, in case the command itself starts with `--`.
+pub(crate) struct Options {
+    pub config: Config,
+    /// Environment variables that aren't settings.
+    pub env: Vec<(&'static str, String)>,
+    pub write_config: Option<PathBuf>,
+    /// Number of arguments taken up by the options, the command follows them.
+    pub len: usize,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> anyhow::Result<Options> {
+        let mut options = Options {
+            config: Config::default(),
+            env: Vec::new(),
+            write_config: None,
+            len: 0,
+        };
+
+        let mut settings = Vec::new();
+
+        let mut i = 0;
+        while let Some(name) = args.get(i) {
+            if name == "--" {
+                i += 1;
+                break;
+            }
+
+            let Some(option) = name.strip_prefix("--") else {
+                break;
+            };
+
+            let Some(value) = args.get(i + 1) else {
+                bail!("Missing value for {name}");
+            };
+
+            match option {
+                "config" => options.env.push(("DOORSTOP_CONFIG", path::absolute(value)?.to_string_lossy().into_owned())),
+                "profile" => options.env.push(("DOORSTOP_PROFILE", value.clone())),
+                "write-config" => options.write_config = Some(PathBuf::from(value)),
+                _ => {
+                    let arg = format!("--doorstop-{option}");
+                    let Some(setting) = SETTINGS.iter().find(|setting| setting.arg == Some(arg.as_str())) else {
+                        bail!("Unknown option {name}");
+                    };
+
+                    settings.push((setting, name, value));
+                }
+            }
+
+            i += 2;
+        }
+
+        options.len = i;
+
+        // Placeholders refer to the game being launched
+        let command = args.get(i).map(path::absolute).transpose()?;
+        let game_dir = match command.as_deref().and_then(Path::parent) {
+            Some(game_dir) => game_dir.to_path_buf(),
+            None => env::current_dir()?,
+        };
+        let executable_name = command
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+
+        let context = LoadContext::from_process(game_dir, executable_name, None)?;
+        options.config.working_dir.clone_from(&context.working_dir);
+
+        for (setting, name, value) in settings {
+            if let Err(message) = options.config.set(&context, setting, value, &Source::CommandLine(name.clone())) {
+                bail!("Invalid value for {name}: {message}");
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Writes the config file if `--write-config` was given, otherwise sets the environment variables for the command to inherit.
+    /// Returns whether the config file was written, in which case nothing should be launched.
+    pub fn apply(&self) -> anyhow::Result<bool> {
+        if let Some(path) = self.write_config.as_ref() {
+            fs::write(path, self.config.to_ini()).with_context(|| format!("Failed to write {}", path.display()))?;
+            return Ok(true);
+        }
+
+        for (name, value) in self.config.to_environment().into_iter().chain(self.env.iter().cloned()) {
+            unsafe {
+                env::set_var(name, value);
+            }
+        }
+
+        Ok(false)
+    }
+}
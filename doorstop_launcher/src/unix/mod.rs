@@ -14,6 +14,7 @@ use libc::execvp;
 
 use crate::{
     get_doorstop_path,
+    options::Options,
     unix::utils::{get_executable_architectures, pick_architecture},
 };
 
@@ -43,8 +44,21 @@ pub unsafe extern "C" fn main(argc: i32, argv: *mut *mut c_char) -> i32 {
 }
 
 fn try_main(args: &mut [*mut c_char]) -> anyhow::Result<i32> {
+    let arguments: Vec<String> = args[1..]
+        .iter()
+        .map(|arg| unsafe { CStr::from_ptr(*arg) }.to_string_lossy().into_owned())
+        .collect();
+
+    let options = Options::parse(&arguments)?;
+    if options.apply()? {
+        return Ok(0);
+    }
+
+    // Keep the first argument in place so the command stays at index 1
+    let args = &mut args[options.len..];
+
     if args.len() <= 1 {
-        eprintln!("usage: doorstop_launcher COMMAND [ARGS]");
+        eprintln!("usage: doorstop_launcher [OPTIONS] COMMAND [ARGS]");
         return Ok(1);
     }
 
@@ -24,7 +24,7 @@ use windows::{
     core::{Error, PCWSTR, PWSTR},
 };
 
-use crate::{ProcessorArchitecture, get_doorstop_path, windows::utils::strip_first_arg};
+use crate::{ProcessorArchitecture, get_doorstop_path, options::Options, windows::utils::strip_first_arg};
 
 mod utils;
 
@@ -68,11 +68,21 @@ unsafe fn assign_to_job(process: HANDLE) -> anyhow::Result<()> {
 
 fn try_main() -> anyhow::Result<ExitCode> {
     unsafe {
+        let arguments: Vec<String> = env::args_os().skip(1).map(|arg| arg.to_string_lossy().into_owned()).collect();
+
+        let options = Options::parse(&arguments)?;
+        if options.apply()? {
+            return Ok(ExitCode::SUCCESS);
+        }
+
         let command_line = GetCommandLineW();
-        let command_line = strip_first_arg(slice::from_raw_parts(command_line.0, command_line.len() + 1));
+        let mut command_line = strip_first_arg(slice::from_raw_parts(command_line.0, command_line.len() + 1));
+        for _ in 0..options.len {
+            command_line = strip_first_arg(command_line);
+        }
 
         if command_line.is_empty() || command_line[0] == 0 {
-            eprintln!("usage: doorstop_launcher COMMAND [ARGS]");
+            eprintln!("usage: doorstop_launcher [OPTIONS] COMMAND [ARGS]");
             return Ok(ExitCode::from(1));
         }
 
@@ -1,6 +1,6 @@
 #![cfg_attr(all(not(test), unix), no_main)]
 
-use std::{env, env::current_dir, fs::read_dir, path::PathBuf};
+use std::{env, env::current_dir, fs::read_dir, path, path::PathBuf};
 
 use anyhow::bail;
 use cfg_if::cfg_if;
@@ -74,8 +74,13 @@ pub fn init() -> anyhow::Result<PathBuf> {
         env::set_var("DOORSTOP_PLAYER", "true");
     }
 
-    let executable = get_executable_path()?;
+    let executable = path::absolute(get_executable_path()?)?;
     env::set_current_dir(executable.parent().unwrap())?;
 
+    // Let doorstop_core know which game it's running, current_exe points to doorstop_player
+    unsafe {
+        env::set_var("DOORSTOP_PLAYER_EXECUTABLE", &executable);
+    }
+
     Ok(executable)
 }
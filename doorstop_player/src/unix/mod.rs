@@ -141,7 +141,9 @@ extern "C" fn main(argc: i32, argv: *mut *mut c_char) -> i32 {
 
             doorstop_core::try_init(unity_player_handle)?;
 
-            Ok(player_main(argc, argv))
+            let (argc, argv) = doorstop_core::rewrite_c_arguments(argc, argv.cast());
+
+            Ok(player_main(argc, argv.cast_mut().cast()))
         }
     }
 
@@ -2,7 +2,6 @@ use windows::{
     Win32::{
         Foundation::{HINSTANCE, HMODULE},
         System::{
-            Environment::GetCommandLineW,
             LibraryLoader::{GetModuleHandleW, GetProcAddress},
             Threading::{GetStartupInfoW, STARTUPINFOW},
         },
@@ -17,7 +16,7 @@ type FnUnityMain = unsafe extern "system" fn(hInstance: HINSTANCE, hPrevInstance
 pub unsafe fn unity_main(unity_player_handle: HMODULE) {
     unsafe {
         let module_handle = GetModuleHandleW(None).unwrap();
-        let command_line = GetCommandLineW();
+        let command_line = doorstop_core::get_command_line();
 
         let mut startup_info = STARTUPINFOW::default();
         GetStartupInfoW(&raw mut startup_info);
@@ -25,11 +24,6 @@ pub unsafe fn unity_main(unity_player_handle: HMODULE) {
         let unity_main: FnUnityMain =
             std::mem::transmute(GetProcAddress(unity_player_handle, s!("UnityMain")).expect("UnityPlayer.dll should contain UnityMain"));
 
-        unity_main(
-            module_handle.into(),
-            HMODULE::default(),
-            PWSTR(command_line.as_ptr().cast_mut()),
-            i32::from(startup_info.wShowWindow),
-        );
+        unity_main(module_handle.into(), HMODULE::default(), command_line, i32::from(startup_info.wShowWindow));
     }
 }